@@ -6,6 +6,8 @@ use std::env;
 use std::fs::File;
 use std::io;
 use std::io::BufReader;
+use std::thread;
+use std::time::Duration;
 
 // Maybe the best way to do this is something like:
 //
@@ -19,45 +21,41 @@ struct WavPlayer {
 
 impl WavPlayer {
     fn write_callback(&mut self, stream: &mut soundio::OutStreamWriter) {
-        let mut frames_left = stream.frame_count_max();
+        let frame_count_max = stream.frame_count_max();
         let was_finished = self.finished;
-        loop {
-            if let Err(e) = stream.begin_write(frames_left) {
-                println!("Error writing to stream: {}", e);
-                return;
-            }
+        let reader = &mut self.reader;
+        let finished = &mut self.finished;
+        let result = stream.write_chunks::<i32, _>(frame_count_max, |mut chunk, _| {
             // Hound's sample conversion is not as awesome as mine. This will fail on floating point types.
-            let mut s = self.reader.samples::<i32>();
+            let mut s = reader.samples::<i32>();
 
-            for f in 0..stream.frame_count() {
-                for c in 0..stream.channel_count() {
+            for f in 0..chunk.frame_count() {
+                for c in 0..chunk.channel_count() {
                     match s.next() {
                         Some(x) => {
-                            stream.set_sample(c, f, x.unwrap() * 1000);
+                            chunk.set(c, f, x.unwrap() * 1000);
                         }
                         None => {
-                            stream.set_sample(c, f, 0);
-                            self.finished = true;
+                            chunk.set(c, f, 0);
+                            *finished = true;
                         }
                     }
                 }
             }
-
-            frames_left -= stream.frame_count();
-            if frames_left <= 0 {
-                break;
-            }
-
-            stream.end_write();
+        });
+        if let Err(e) = result {
+            println!("Error writing to stream: {}", e);
         }
-        if self.finished != was_finished {
-            //		stream.wakeup();
+        if self.finished && !was_finished {
+            // Stop at the end of the file instead of playing silence.
+            if let Err(e) = stream.pause(true) {
+                println!("Error pausing stream: {}", e);
+            }
+            stream.request_stop();
         }
     }
 }
 
-// TODO: I need some interior mutability and a mutex to make the write_callback work nicely.
-
 // Print sound soundio debug info and play back a sound.
 fn play(filename: &str) -> Result<(), String> {
     // Try to open the file.
@@ -133,11 +131,20 @@ fn play(filename: &str) -> Result<(), String> {
     println!("Starting stream");
     output_stream.start()?;
 
-    // Wait for key presses.
+    // Stop when the file ends or the user presses enter.
     println!("Press enter to stop playback");
-    let stdin = io::stdin();
-    let input = &mut String::new();
-    let _ = stdin.read_line(input);
+    let handle = output_stream.handle();
+    let stdin_handle = handle.clone();
+    thread::spawn(move || {
+        let stdin = io::stdin();
+        let input = &mut String::new();
+        let _ = stdin.read_line(input);
+        stdin_handle.request_stop();
+    });
+
+    while !handle.stop_requested() {
+        thread::sleep(Duration::from_millis(100));
+    }
 
     Ok(())
 }
@@ -3,43 +3,11 @@ extern crate hound;
 extern crate soundio;
 
 use std::env;
-use std::fs::File;
 use std::io;
-use std::io::BufWriter;
-
-struct WavRecorder {
-    writer: hound::WavWriter<BufWriter<File>>,
-}
-
-impl WavRecorder {
-    fn read_callback(&mut self, stream: &mut soundio::InStreamReader) {
-        let mut frames_left = stream.frame_count_max();
-
-        // libsoundio reads samples in chunks, so we need to loop until there's nothing to read.
-        loop {
-            if let Err(e) = stream.begin_read(frames_left) {
-                println!("Error reading from stream: {}", e);
-                return;
-            }
-            for f in 0..stream.frame_count() {
-                for c in 0..stream.channel_count() {
-                    // In reality you shouldn't write to disk in the callback, but have some buffer instead.
-                    match self.writer.write_sample(stream.sample::<i16>(c, f)) {
-                        Ok(_) => {}
-                        Err(e) => println!("Error: {}", e),
-                    }
-                }
-            }
-
-            frames_left -= stream.frame_count();
-            if frames_left <= 0 {
-                break;
-            }
-
-            stream.end_read();
-        }
-    }
-}
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::thread;
+use std::time::Duration;
 
 // Print sound soundio debug info and record a sound.
 fn record(filename: &str) -> Result<(), String> {
@@ -55,7 +23,7 @@ fn record(filename: &str) -> Result<(), String> {
     };
 
     // Try to open the output file.
-    let writer = hound::WavWriter::create(filename, spec).map_err(|x| x.to_string())?;
+    let mut writer = hound::WavWriter::create(filename, spec).map_err(|x| x.to_string())?;
 
     println!("Soundio version: {}", soundio::version_string());
 
@@ -67,8 +35,6 @@ fn record(filename: &str) -> Result<(), String> {
 
     // We have to flush events so we can scan devices.
     ctx.flush_events();
-    // I guess these are always signed little endian?
-    let soundio_format = soundio::Format::S16LE;
 
     let default_layout = soundio::ChannelLayout::get_default(channels as _);
     println!(
@@ -86,27 +52,36 @@ fn record(filename: &str) -> Result<(), String> {
         if input_dev.is_raw() { "raw" } else { "cooked" }
     );
 
-    let mut recorder = WavRecorder { writer: writer };
-
     println!("Opening default input stream");
-    let mut input_stream = input_dev.open_instream(
-        sample_rate as _,
-        soundio_format,
-        default_layout,
-        0.1,
-        |x| recorder.read_callback(x),
-        None::<fn()>,
-        None::<fn(soundio::Error)>,
-    )?;
-
-    println!("Starting stream");
-    input_stream.start()?;
-
-    // Wait for the user to press a key.
+    let mut input_stream = input_dev
+        .instream_builder()
+        .sample_rate(sample_rate as _)
+        .layout(default_layout)
+        .software_latency(0.1)
+        .open_blocking::<i16>(1.0)?;
+
+    // Wait for the user to press a key on another thread, so that this one can write
+    // the captured audio to disk.
     println!("Press enter to stop recording");
-    let stdin = io::stdin();
-    let input = &mut String::new();
-    let _ = stdin.read_line(input);
+    let stop = Arc::new(AtomicBool::new(false));
+    let stop_setter = stop.clone();
+    thread::spawn(move || {
+        let stdin = io::stdin();
+        let input = &mut String::new();
+        let _ = stdin.read_line(input);
+        stop_setter.store(true, Ordering::Relaxed);
+    });
+
+    let mut samples = vec![0i16; 4096 * input_stream.channel_count()];
+    while !stop.load(Ordering::Relaxed) {
+        let count = input_stream.read_timeout(&mut samples, Duration::from_millis(100))?;
+        for &sample in &samples[..count] {
+            writer.write_sample(sample).map_err(|x| x.to_string())?;
+        }
+    }
+
+    println!("Overflows: {}", input_stream.overflow_count());
+    writer.finalize().map_err(|x| x.to_string())?;
 
     Ok(())
 }
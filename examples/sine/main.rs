@@ -12,28 +12,19 @@ struct SineWavePlayer {
 
 impl SineWavePlayer {
     fn write_callback(&mut self, stream: &mut soundio::OutStreamWriter) {
-        let mut frames_left = stream.frame_count_max();
+        let frame_count_max = stream.frame_count_max();
+        let phase_step = self.frequency / stream.sample_rate() as f64 * 2.0 * PI;
 
-        loop {
-            if let Err(e) = stream.begin_write(frames_left) {
-                println!("Error writing to stream: {}", e);
-                return;
-            }
-            let phase_step = self.frequency / stream.sample_rate() as f64 * 2.0 * PI;
-
-            for c in 0..stream.channel_count() {
-                for f in 0..stream.frame_count() {
-                    stream.set_sample(c, f, (self.phase.sin() * self.amplitude) as f32);
+        let result = stream.write_chunks::<f32, _>(frame_count_max, |mut chunk, _| {
+            for c in 0..chunk.channel_count() {
+                for f in 0..chunk.frame_count() {
+                    chunk.set(c, f, (self.phase.sin() * self.amplitude) as f32);
                     self.phase += phase_step;
                 }
             }
-
-            frames_left -= stream.frame_count();
-            if frames_left <= 0 {
-                break;
-            }
-
-            stream.end_write();
+        });
+        if let Err(e) = result {
+            println!("Error writing to stream: {}", e);
         }
     }
 }
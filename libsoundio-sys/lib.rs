@@ -1024,6 +1024,16 @@ extern "C" {
         out_latency: *mut c_double,
     ) -> c_int;
 
+    // Set the volume of the stream, from 0.0 to 1.0.
+    //
+    // Possible errors:
+    // * #SoundIoErrorIncompatibleBackend - backend does not support setting the volume.
+    // * #SoundIoErrorIncompatibleDevice - device does not support setting the volume.
+    pub fn soundio_outstream_set_volume(
+        outstream: *mut SoundIoOutStream,
+        volume: c_double,
+    ) -> c_int;
+
     // Input Streams
     // Allocates memory and sets defaults. Next you should fill out the struct fields
     // and then call ::soundio_instream_open. Sets all fields to defaults.
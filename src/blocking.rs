@@ -0,0 +1,569 @@
+extern crate libsoundio_sys as raw;
+
+use super::buffer::*;
+use super::error::*;
+use super::instream::*;
+use super::outstream::*;
+use super::ring_buffer::*;
+
+use std::cmp::min;
+use std::marker::PhantomData;
+use std::mem;
+use std::sync::atomic::{AtomicBool, AtomicU64, AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::{Duration, Instant};
+
+/// State shared between a blocking stream and its callbacks. Everything is atomic so that
+/// the callbacks never block.
+pub(crate) struct BlockingShared {
+    // The number of underflows or overflows.
+    xruns: AtomicUsize,
+    // The latency from the last callback in seconds, stored as `f64` bits.
+    latency: AtomicU64,
+    // Set when the error callback is called. The stream can't be used after this.
+    errored: AtomicBool,
+}
+
+impl BlockingShared {
+    pub(crate) fn new() -> BlockingShared {
+        BlockingShared {
+            xruns: AtomicUsize::new(0),
+            latency: AtomicU64::new(0),
+            errored: AtomicBool::new(false),
+        }
+    }
+
+    pub(crate) fn xrun(&self) {
+        self.xruns.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub(crate) fn set_errored(&self) {
+        self.errored.store(true, Ordering::Release);
+    }
+
+    fn set_latency(&self, latency: f64) {
+        self.latency.store(latency.to_bits(), Ordering::Relaxed);
+    }
+
+    fn latency(&self) -> f64 {
+        f64::from_bits(self.latency.load(Ordering::Relaxed))
+    }
+
+    fn check(&self) -> Result<()> {
+        if self.errored.load(Ordering::Acquire) {
+            Err(Error::Streaming)
+        } else {
+            Ok(())
+        }
+    }
+}
+
+/// Passes a value to a callback after the stream has been opened, e.g. a ring buffer
+/// that is sized from the stream's actual sample rate and layout. The value must be
+/// sent before the stream is started.
+pub(crate) struct Handoff<T> {
+    slot: Arc<Mutex<Option<T>>>,
+}
+
+/// The callback's side of a `Handoff`.
+pub(crate) struct HandoffReceiver<T> {
+    slot: Arc<Mutex<Option<T>>>,
+    value: Option<T>,
+}
+
+pub(crate) fn handoff<T>() -> (Handoff<T>, HandoffReceiver<T>) {
+    let slot = Arc::new(Mutex::new(None));
+    (
+        Handoff { slot: slot.clone() },
+        HandoffReceiver { slot, value: None },
+    )
+}
+
+impl<T> Handoff<T> {
+    pub(crate) fn send(self, value: T) {
+        *self.slot.lock().unwrap_or_else(|e| e.into_inner()) = Some(value);
+    }
+}
+
+impl<T> HandoffReceiver<T> {
+    /// The value, or `None` if it hasn't been sent. This never blocks.
+    pub(crate) fn get(&mut self) -> Option<&mut T> {
+        if self.value.is_none() {
+            if let Ok(mut slot) = self.slot.try_lock() {
+                self.value = slot.take();
+            }
+        }
+        self.value.as_mut()
+    }
+}
+
+/// The size in bytes of a ring buffer holding `duration` seconds of audio.
+pub(crate) fn ring_buffer_size<T>(duration: f64, sample_rate: i32, channel_count: usize) -> usize {
+    let frames = (duration * f64::from(sample_rate)).ceil().max(1.0) as usize;
+    frames * channel_count * mem::size_of::<T>()
+}
+
+// How long `BlockingOutStream::drain()` waits for the callback to take any audio before
+// giving up.
+const DRAIN_STALL_TIMEOUT: Duration = Duration::from_secs(1);
+
+// How long to sleep while waiting for the callback. A fraction of the software latency,
+// so that we don't wake up too often on high latency backends.
+fn poll_interval(software_latency: f64) -> Duration {
+    Duration::from_secs_f64((software_latency / 4.0).clamp(0.001, 0.01))
+}
+
+/// The write callback used by `BlockingOutStream`. It copies as many whole frames as are
+/// queued to the stream, padding with silence up to `frame_count_min()`.
+pub(crate) fn blocking_write_callback<T: NativeSample>(
+    stream: &mut OutStreamWriter,
+    consumer: &mut Consumer,
+    shared: &BlockingShared,
+) {
+    let channel_count = stream.channel_count();
+    let available = consumer.fill_samples::<T>() / channel_count;
+    // The writer didn't keep up, so some silence has to be played.
+    if available > 0 && available < stream.frame_count_min() {
+        shared.xrun();
+    }
+
+    let mut frames_left = available
+        .max(stream.frame_count_min())
+        .min(stream.frame_count_max());
+    let silence = T::from_f32(0.0);
+
+    while frames_left > 0 {
+        let frame_count = match stream.begin_write(frames_left) {
+            Ok(0) | Err(_) => break,
+            Ok(n) => n,
+        };
+
+        if let Ok(buf) = stream.interleaved_slice_mut::<T>() {
+            let read = consumer.read(buf);
+            for x in &mut buf[read..] {
+                *x = silence;
+            }
+        } else {
+            let mut frame_buf = [silence; raw::SOUNDIO_MAX_CHANNELS];
+            for mut frame in stream.frames_mut::<T>() {
+                let read = consumer.read(&mut frame_buf[..channel_count]);
+                for (c, &x) in frame_buf[..channel_count].iter().enumerate() {
+                    frame.set(c, if c < read { x } else { silence });
+                }
+            }
+        }
+
+        match stream.end_write() {
+            Ok(()) => {}
+            Err(Error::Underflow) => {
+                shared.xrun();
+                break;
+            }
+            Err(_) => break,
+        }
+        frames_left -= frame_count;
+    }
+
+    if let Ok(latency) = stream.get_latency() {
+        shared.set_latency(latency);
+    }
+}
+
+/// An output stream that you write to like a file, instead of supplying a write callback.
+///
+/// It is opened with `OutStreamBuilder::open_blocking()` and starts playing immediately.
+/// Samples written with `write()` are queued in a `RingBuffer` and copied to the device
+/// by an internal write callback. Samples are interleaved, i.e. LRLRLR for stereo, and
+/// the stream format is always `T::FORMAT`.
+///
+/// If you don't write fast enough silence is played and `underflow_count()` is
+/// incremented.
+///
+/// # Examples
+///
+/// ```
+/// # fn foo() -> Result<(), soundio::Error> {
+/// let mut ctx = soundio::Context::new();
+/// ctx.connect_backend(soundio::Backend::Dummy)?;
+/// ctx.flush_events();
+/// let dev = ctx.default_output_device()?;
+///
+/// let mut stream = dev
+///     .outstream_builder()
+///     .sample_rate(44100)
+///     .open_blocking::<f32>(0.2)?;
+///
+/// let channel_count = stream.channel_count();
+/// let mut phase = 0.0f32;
+/// let mut samples = Vec::new();
+/// for _ in 0..44100 {
+///     phase += 440.0 / 44100.0 * 2.0 * std::f32::consts::PI;
+///     for _ in 0..channel_count {
+///         samples.push(phase.sin() * 0.5);
+///     }
+/// }
+/// stream.write(&samples)?;
+/// stream.drain()?;
+/// println!("{} underflows", stream.underflow_count());
+/// # Ok(())
+/// # }
+/// ```
+pub struct BlockingOutStream<'a, T: NativeSample> {
+    // The stream is dropped first so that the callback stops before the buffer is freed.
+    stream: OutStream<'a>,
+    producer: Producer,
+    shared: Arc<BlockingShared>,
+    channel_count: usize,
+    paused: bool,
+    phantom: PhantomData<T>,
+}
+
+impl<'a, T: NativeSample> BlockingOutStream<'a, T> {
+    pub(crate) fn new(
+        mut stream: OutStream<'a>,
+        producer: Producer,
+        shared: Arc<BlockingShared>,
+    ) -> Result<BlockingOutStream<'a, T>> {
+        let channel_count = unsafe { (*stream.userdata.outstream).layout.channel_count as usize };
+        stream.start()?;
+        Ok(BlockingOutStream {
+            stream,
+            producer,
+            shared,
+            channel_count,
+            paused: false,
+            phantom: PhantomData,
+        })
+    }
+
+    /// Write interleaved samples, blocking until they have all been queued. `samples` must
+    /// contain a whole number of frames, otherwise this panics.
+    ///
+    /// # Errors
+    ///
+    /// * `Error::Streaming` - the stream has failed and must be dropped.
+    pub fn write(&mut self, samples: &[T]) -> Result<()> {
+        let mut samples = samples;
+        while !samples.is_empty() {
+            self.shared.check()?;
+            let written = self.try_write(samples);
+            samples = &samples[written..];
+            if !samples.is_empty() {
+                thread::sleep(self.poll_interval());
+            }
+        }
+        Ok(())
+    }
+
+    /// Queue as many whole frames from `samples` as there is space for without blocking,
+    /// and return the number of samples written. Panics if `samples` does not contain a
+    /// whole number of frames.
+    pub fn try_write(&mut self, samples: &[T]) -> usize {
+        assert!(
+            samples
+                .chunks_exact(self.channel_count)
+                .remainder()
+                .is_empty(),
+            "Samples must contain whole frames"
+        );
+        let free = self.producer.free_samples::<T>();
+        let count = min(samples.len(), free - free % self.channel_count);
+        self.producer.write(&samples[..count])
+    }
+
+    /// Block until all queued audio has been played. This waits for the buffer to empty
+    /// and then for the latency reported by `OutStreamWriter::get_latency()`.
+    ///
+    /// # Errors
+    ///
+    /// * `Error::Streaming` - the stream has failed and must be dropped.
+    /// * `Error::Invalid` - the stream was paused with `pause()`.
+    /// * `Error::Interrupted` - the device hasn't taken any audio for a second, for
+    ///   example because the stream was paused with an `OutStreamHandle`. The audio
+    ///   is still queued.
+    pub fn drain(&mut self) -> Result<()> {
+        if self.paused {
+            return Err(Error::Invalid);
+        }
+        let frame_bytes = self.channel_count * mem::size_of::<T>();
+        let mut queued = self.queued_bytes();
+        let mut last_progress = Instant::now();
+        while queued >= frame_bytes {
+            self.shared.check()?;
+            if last_progress.elapsed() > DRAIN_STALL_TIMEOUT {
+                return Err(Error::Interrupted);
+            }
+            thread::sleep(self.poll_interval());
+            let now_queued = self.queued_bytes();
+            if now_queued < queued {
+                last_progress = Instant::now();
+            }
+            queued = now_queued;
+        }
+        thread::sleep(Duration::from_secs_f64(self.shared.latency().max(0.0)));
+        self.shared.check()
+    }
+
+    /// The number of frames queued and not yet passed to the device.
+    pub fn queued_frames(&self) -> usize {
+        self.queued_bytes() / (self.channel_count * mem::size_of::<T>())
+    }
+
+    /// The number of times silence had to be played because not enough audio was queued,
+    /// including underflows reported by the backend.
+    pub fn underflow_count(&self) -> usize {
+        self.shared.xruns.load(Ordering::Relaxed)
+    }
+
+    /// The number of channels in each frame.
+    pub fn channel_count(&self) -> usize {
+        self.channel_count
+    }
+
+    /// The sample rate in frames per second.
+    pub fn sample_rate(&self) -> i32 {
+        self.stream.sample_rate()
+    }
+
+    /// Pause or unpause the stream. See `OutStream::pause()`.
+    pub fn pause(&mut self, pause: bool) -> Result<()> {
+        self.stream.pause(pause)?;
+        self.paused = pause;
+        Ok(())
+    }
+
+    /// The underlying stream.
+    pub fn stream(&self) -> &OutStream<'a> {
+        &self.stream
+    }
+
+    fn queued_bytes(&self) -> usize {
+        self.producer.capacity() - self.producer.free_count()
+    }
+
+    fn poll_interval(&self) -> Duration {
+        poll_interval(self.stream.software_latency())
+    }
+}
+
+/// The read callback used by `BlockingInStream`. It copies every captured frame to the
+/// ring buffer, or silence for holes. Frames that don't fit are dropped and counted as
+/// an overflow.
+pub(crate) fn blocking_read_callback<T: NativeSample>(
+    stream: &mut InStreamReader,
+    producer: &mut Producer,
+    shared: &BlockingShared,
+) {
+    let channel_count = stream.channel_count();
+    let silence = [T::from_f32(0.0); raw::SOUNDIO_MAX_CHANNELS];
+    let mut frames_left = stream.frame_count_max();
+
+    while frames_left > 0 {
+        let frame_count = match stream.begin_read(frames_left) {
+            Ok(0) | Err(_) => break,
+            Ok(n) => n,
+        };
+
+        // The reader didn't keep up, so drop the frames that don't fit.
+        let free_frames = producer.free_samples::<T>() / channel_count;
+        if free_frames < frame_count {
+            shared.xrun();
+        }
+        let frames = min(frame_count, free_frames);
+
+        match stream.interleaved_slice::<T>() {
+            Ok(buf) => {
+                producer.write(&buf[..frames * channel_count]);
+            }
+            Err(SliceError::Hole) => {
+                for _ in 0..frames {
+                    producer.write(&silence[..channel_count]);
+                }
+            }
+            Err(_) => {
+                let mut frame_buf = silence;
+                for frame in stream.frames::<T>().take(frames) {
+                    for (c, x) in frame_buf[..channel_count].iter_mut().enumerate() {
+                        *x = frame.get(c);
+                    }
+                    producer.write(&frame_buf[..channel_count]);
+                }
+            }
+        }
+
+        if stream.end_read().is_err() {
+            break;
+        }
+        frames_left -= frame_count;
+    }
+}
+
+/// An input stream that you read from like a file, instead of supplying a read callback.
+///
+/// It is opened with `InStreamBuilder::open_blocking()` and starts recording immediately.
+/// Captured frames are queued in a `RingBuffer` by an internal read callback until you
+/// call `read()`. Samples are interleaved, i.e. LRLRLR for stereo, and the stream format
+/// is always `T::FORMAT`. Holes in the captured data are replaced with silence.
+///
+/// If you don't read fast enough the newest frames are dropped and `overflow_count()`
+/// is incremented.
+///
+/// # Examples
+///
+/// ```
+/// # fn foo() -> Result<(), soundio::Error> {
+/// use std::time::Duration;
+///
+/// let mut ctx = soundio::Context::new();
+/// ctx.connect_backend(soundio::Backend::Dummy)?;
+/// ctx.flush_events();
+/// let dev = ctx.default_input_device()?;
+///
+/// let mut stream = dev
+///     .instream_builder()
+///     .sample_rate(44100)
+///     .open_blocking::<i16>(1.0)?;
+///
+/// let mut samples = vec![0i16; 1024 * stream.channel_count()];
+/// for _ in 0..10 {
+///     let count = stream.read_timeout(&mut samples, Duration::from_millis(500))?;
+///     println!("Captured {} frames", count / stream.channel_count());
+/// }
+/// println!("{} overflows", stream.overflow_count());
+/// # Ok(())
+/// # }
+/// ```
+pub struct BlockingInStream<'a, T: NativeSample> {
+    // The stream is dropped first so that the callback stops before the buffer is freed.
+    stream: InStream<'a>,
+    consumer: Consumer,
+    shared: Arc<BlockingShared>,
+    channel_count: usize,
+    phantom: PhantomData<T>,
+}
+
+impl<'a, T: NativeSample> BlockingInStream<'a, T> {
+    pub(crate) fn new(
+        mut stream: InStream<'a>,
+        consumer: Consumer,
+        shared: Arc<BlockingShared>,
+    ) -> Result<BlockingInStream<'a, T>> {
+        let channel_count = unsafe { (*stream.userdata.instream).layout.channel_count as usize };
+        stream.start()?;
+        Ok(BlockingInStream {
+            stream,
+            consumer,
+            shared,
+            channel_count,
+            phantom: PhantomData,
+        })
+    }
+
+    /// Read interleaved samples into `samples`, blocking until at least one frame is
+    /// available, and return the number of samples read. This is always a whole number
+    /// of frames. Panics if `samples` is shorter than one frame.
+    ///
+    /// # Errors
+    ///
+    /// * `Error::Streaming` - the stream has failed and must be dropped.
+    pub fn read(&mut self, samples: &mut [T]) -> Result<usize> {
+        loop {
+            let count = self.try_read(samples);
+            if count > 0 {
+                return Ok(count);
+            }
+            self.shared.check()?;
+            thread::sleep(self.poll_interval());
+        }
+    }
+
+    /// Like `read()`, but return `Ok(0)` if no frames are available within `timeout`.
+    ///
+    /// # Errors
+    ///
+    /// * `Error::Streaming` - the stream has failed and must be dropped.
+    pub fn read_timeout(&mut self, samples: &mut [T], timeout: Duration) -> Result<usize> {
+        let deadline = Instant::now() + timeout;
+        loop {
+            let count = self.try_read(samples);
+            if count > 0 {
+                return Ok(count);
+            }
+            self.shared.check()?;
+            let now = Instant::now();
+            if now >= deadline {
+                return Ok(0);
+            }
+            thread::sleep(min(self.poll_interval(), deadline - now));
+        }
+    }
+
+    /// Read as many whole frames into `samples` as are available without blocking, and
+    /// return the number of samples read. Panics if `samples` is shorter than one frame.
+    pub fn try_read(&mut self, samples: &mut [T]) -> usize {
+        assert!(
+            samples.len() >= self.channel_count,
+            "Samples must fit at least one frame"
+        );
+        let len = samples.len() - samples.len() % self.channel_count;
+        self.consumer.read(&mut samples[..len])
+    }
+
+    /// The number of captured frames that can be read without blocking.
+    pub fn available_frames(&self) -> usize {
+        self.consumer.fill_samples::<T>() / self.channel_count
+    }
+
+    /// The number of times captured frames were lost, either because `read()` wasn't
+    /// called often enough or because the backend reported an overflow.
+    pub fn overflow_count(&self) -> usize {
+        self.shared.xruns.load(Ordering::Relaxed)
+    }
+
+    /// The number of channels in each frame.
+    pub fn channel_count(&self) -> usize {
+        self.channel_count
+    }
+
+    /// The sample rate in frames per second.
+    pub fn sample_rate(&self) -> i32 {
+        self.stream.sample_rate()
+    }
+
+    /// Pause or unpause the stream. See `InStream::pause()`.
+    pub fn pause(&mut self, pause: bool) -> Result<()> {
+        self.stream.pause(pause)
+    }
+
+    /// The underlying stream.
+    pub fn stream(&self) -> &InStream<'a> {
+        &self.stream
+    }
+
+    fn poll_interval(&self) -> Duration {
+        poll_interval(self.stream.software_latency())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn sizes() {
+        assert_eq!(ring_buffer_size::<f32>(0.5, 48000, 2), 24000 * 2 * 4);
+        assert_eq!(ring_buffer_size::<i16>(0.0, 48000, 1), 2);
+        assert_eq!(poll_interval(2.0), Duration::from_millis(10));
+        assert_eq!(poll_interval(0.0), Duration::from_millis(1));
+    }
+
+    #[test]
+    fn handoff_after_open() {
+        let (handoff, mut receiver) = handoff::<u32>();
+        assert!(receiver.get().is_none());
+        handoff.send(7);
+        *receiver.get().unwrap() += 1;
+        assert_eq!(receiver.get(), Some(&mut 8));
+    }
+}
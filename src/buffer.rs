@@ -0,0 +1,812 @@
+extern crate libsoundio_sys as raw;
+
+use super::format::*;
+use super::sample::*;
+
+use std::error;
+use std::fmt;
+use std::marker::PhantomData;
+use std::mem;
+use std::ops::Index;
+use std::ptr;
+use std::result;
+use std::slice;
+
+/// `NativeSample` is implemented for the sample types that libsoundio can store
+/// directly in memory, i.e. where there is a `Format` whose in-memory representation is
+/// exactly that Rust type. This is what allows `OutStreamWriter` and `InStreamReader`
+/// to hand out `&mut [T]` and `&[T]` slices instead of converting every sample.
+///
+/// The 24-bit formats are not included because `u24` and `i24` are not guaranteed to
+/// have the same representation as the 32-bit words libsoundio uses.
+///
+/// # Safety
+///
+/// The buffer slices, frame iterators, typed streams and ring buffers read and write
+/// `T` values directly in memory that libsoundio lays out according to `FORMAT`. An
+/// implementation must therefore have exactly the size and representation of one
+/// sample in `FORMAT`, and every bit pattern of that size must be a valid value of the
+/// type.
+pub unsafe trait NativeSample: Sample + Copy {
+    /// The native endian format with the same representation as this type.
+    const FORMAT: Format;
+}
+
+unsafe impl NativeSample for i8 {
+    const FORMAT: Format = Format::S8;
+}
+unsafe impl NativeSample for u8 {
+    const FORMAT: Format = Format::U8;
+}
+unsafe impl NativeSample for i16 {
+    const FORMAT: Format = native::S16NE;
+}
+unsafe impl NativeSample for u16 {
+    const FORMAT: Format = native::U16NE;
+}
+unsafe impl NativeSample for i32 {
+    const FORMAT: Format = native::S32NE;
+}
+unsafe impl NativeSample for u32 {
+    const FORMAT: Format = native::U32NE;
+}
+unsafe impl NativeSample for f32 {
+    const FORMAT: Format = native::Float32NE;
+}
+unsafe impl NativeSample for f64 {
+    const FORMAT: Format = native::Float64NE;
+}
+
+/// The reason a typed slice of a stream buffer is not available. This is returned
+/// by functions like `OutStreamWriter::channel_slice_mut()`. In all cases you can
+/// still fall back to the per-sample functions, e.g. `OutStreamWriter::set_sample()`.
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub enum SliceError {
+    /// `begin_write()` or `begin_read()` has not been called yet.
+    NotStarted,
+    /// The requested type does not match the stream format.
+    FormatMismatch {
+        /// The format of the stream.
+        stream: Format,
+        /// The format of the type you asked for.
+        requested: Format,
+    },
+    /// The samples of a channel are not next to each other in memory (the channel
+    /// area `step` is not the sample size), so a per-channel slice is impossible.
+    NotContiguous,
+    /// The channels are not interleaved one after another in a single buffer, so an
+    /// interleaved slice is impossible.
+    NotInterleaved,
+    /// The buffer is not correctly aligned for the requested type.
+    Misaligned,
+    /// There is a hole in the captured data, so there are no samples to look at.
+    /// This only happens when reading from an `InStream`.
+    Hole,
+}
+
+impl fmt::Display for SliceError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            SliceError::NotStarted => f.write_str("Read or write has not been started"),
+            SliceError::FormatMismatch { stream, requested } => write!(
+                f,
+                "Stream format is {} but {} was requested",
+                stream, requested
+            ),
+            SliceError::NotContiguous => f.write_str("Channel samples are not contiguous"),
+            SliceError::NotInterleaved => f.write_str("Channels are not interleaved"),
+            SliceError::Misaligned => f.write_str("Buffer is misaligned for sample type"),
+            SliceError::Hole => f.write_str("Captured data has a hole"),
+        }
+    }
+}
+
+impl error::Error for SliceError {}
+
+fn check_format<T: NativeSample>(format: Format) -> result::Result<(), SliceError> {
+    if format != T::FORMAT {
+        return Err(SliceError::FormatMismatch {
+            stream: format,
+            requested: T::FORMAT,
+        });
+    }
+    Ok(())
+}
+
+/// Get a pointer to the first sample of `channel`, checking that its samples can be
+/// viewed as a contiguous `[T]`.
+pub(crate) fn channel_ptr<T: NativeSample>(
+    areas: &[raw::SoundIoChannelArea],
+    format: Format,
+    channel: usize,
+) -> result::Result<*mut T, SliceError> {
+    check_format::<T>(format)?;
+
+    let area = &areas[channel];
+    if area.ptr.is_null() {
+        return Err(SliceError::Hole);
+    }
+    if area.step as usize != mem::size_of::<T>() {
+        return Err(SliceError::NotContiguous);
+    }
+    if (area.ptr as usize & (mem::align_of::<T>() - 1)) != 0 {
+        return Err(SliceError::Misaligned);
+    }
+    Ok(area.ptr as *mut T)
+}
+
+/// Get a pointer to the first sample of the first channel, checking that all of the
+/// channels can be viewed as a single interleaved `[T]`.
+pub(crate) fn interleaved_ptr<T: NativeSample>(
+    areas: &[raw::SoundIoChannelArea],
+    format: Format,
+) -> result::Result<*mut T, SliceError> {
+    check_format::<T>(format)?;
+
+    if areas[0].ptr.is_null() {
+        return Err(SliceError::Hole);
+    }
+
+    let sample_size = mem::size_of::<T>();
+    let base = areas[0].ptr as usize;
+    for (c, area) in areas.iter().enumerate() {
+        if area.step as usize != sample_size * areas.len()
+            || area.ptr as usize != base + c * sample_size
+        {
+            return Err(SliceError::NotInterleaved);
+        }
+    }
+    if (base & (mem::align_of::<T>() - 1)) != 0 {
+        return Err(SliceError::Misaligned);
+    }
+    Ok(areas[0].ptr as *mut T)
+}
+
+/// Check that the samples of `channel` can be viewed as a strided sequence of `T`, and
+/// return the pointer to the first one and the step in bytes.
+pub(crate) fn strided_ptr<T: NativeSample>(
+    areas: &[raw::SoundIoChannelArea],
+    format: Format,
+    channel: usize,
+) -> result::Result<(*mut T, usize), SliceError> {
+    check_format::<T>(format)?;
+
+    let area = &areas[channel];
+    if area.ptr.is_null() {
+        return Err(SliceError::Hole);
+    }
+    let align_mask = mem::align_of::<T>() - 1;
+    if (area.ptr as usize & align_mask) != 0 || (area.step as usize & align_mask) != 0 {
+        return Err(SliceError::Misaligned);
+    }
+    Ok((area.ptr as *mut T, area.step as usize))
+}
+
+/// A read-only view of the samples of one channel. Unlike a slice the samples need
+/// not be next to each other in memory; there are `step` bytes between each one. This
+/// is what you get for an interleaved buffer.
+///
+/// It is returned by `InStreamReader::channel_view()`.
+pub struct ChannelView<'b, T: 'b> {
+    ptr: *const u8,
+    step: usize,
+    len: usize,
+    phantom: PhantomData<&'b [T]>,
+}
+
+impl<'b, T: NativeSample> ChannelView<'b, T> {
+    /// Create a view. The caller must ensure that `len` samples of `T`, `step` bytes
+    /// apart and correctly aligned, are valid for reading for `'b`.
+    pub(crate) unsafe fn new(ptr: *const T, step: usize, len: usize) -> ChannelView<'b, T> {
+        ChannelView {
+            ptr: ptr as *const u8,
+            step,
+            len,
+            phantom: PhantomData,
+        }
+    }
+
+    /// The number of samples, which is the number of frames.
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    /// Returns `true` if there are no samples.
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Get a sample, or `None` if `frame` is out of range.
+    pub fn get(&self, frame: usize) -> Option<&'b T> {
+        if frame < self.len {
+            unsafe { Some(&*(self.ptr.add(frame * self.step) as *const T)) }
+        } else {
+            None
+        }
+    }
+
+    /// Iterate over the samples.
+    pub fn iter(&self) -> ChannelViewIter<'b, T> {
+        ChannelViewIter {
+            view: ChannelView {
+                ptr: self.ptr,
+                step: self.step,
+                len: self.len,
+                phantom: PhantomData,
+            },
+            frame: 0,
+        }
+    }
+
+    /// Copy the samples into `dst`, which must be the same length as the view.
+    /// If the samples are contiguous this is a single `copy_from_slice()`.
+    pub fn copy_to_slice(&self, dst: &mut [T]) {
+        assert_eq!(dst.len(), self.len, "Destination length doesn't match view");
+        if self.step == mem::size_of::<T>() {
+            let src = unsafe { slice::from_raw_parts(self.ptr as *const T, self.len) };
+            dst.copy_from_slice(src);
+        } else {
+            for (d, s) in dst.iter_mut().zip(self.iter()) {
+                *d = s;
+            }
+        }
+    }
+}
+
+impl<'b, T: NativeSample> Index<usize> for ChannelView<'b, T> {
+    type Output = T;
+
+    fn index(&self, frame: usize) -> &T {
+        self.get(frame).expect("Frame out of range")
+    }
+}
+
+impl<'b, T: NativeSample> IntoIterator for &ChannelView<'b, T> {
+    type Item = T;
+    type IntoIter = ChannelViewIter<'b, T>;
+
+    fn into_iter(self) -> ChannelViewIter<'b, T> {
+        self.iter()
+    }
+}
+
+/// An iterator over the samples in a `ChannelView`.
+pub struct ChannelViewIter<'b, T: 'b> {
+    view: ChannelView<'b, T>,
+    frame: usize,
+}
+
+impl<'b, T: NativeSample> Iterator for ChannelViewIter<'b, T> {
+    type Item = T;
+
+    fn next(&mut self) -> Option<T> {
+        let sample = self.view.get(self.frame).cloned();
+        if sample.is_some() {
+            self.frame += 1;
+        }
+        sample
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        let remaining = self.view.len - self.frame;
+        (remaining, Some(remaining))
+    }
+}
+
+impl<'b, T: NativeSample> ExactSizeIterator for ChannelViewIter<'b, T> {}
+
+/// An empty channel area, used when there are no frames.
+pub(crate) const NO_AREA: raw::SoundIoChannelArea = raw::SoundIoChannelArea {
+    ptr: ptr::null_mut(),
+    step: 0,
+};
+
+/// Reads one raw sample and converts it to `T`.
+pub(crate) type ReadFn<T> = unsafe fn(*const u8) -> T;
+/// Converts `T` and writes it as one raw sample.
+pub(crate) type WriteFn<T> = unsafe fn(T, *mut u8);
+
+// Generate a reading and a writing function for each format, so that the `match` on the
+// format can be done once per block rather than once per sample.
+macro_rules! conversion_fns {
+    ($($read:ident, $write:ident => $ty:ty, $from_raw:ident, $to_raw:ident, $from:ident, $to:ident;)*) => {
+        $(
+            unsafe fn $read<T: Sample>(ptr: *const u8) -> T {
+                T::$from(<$ty>::$from_raw(ptr))
+            }
+            unsafe fn $write<T: Sample>(v: T, ptr: *mut u8) {
+                <$ty>::$to_raw(T::$to(v), ptr)
+            }
+        )*
+    };
+}
+
+conversion_fns! {
+    read_s8, write_s8 => i8, from_raw_le, to_raw_le, from_i8, to_i8;
+    read_u8, write_u8 => u8, from_raw_le, to_raw_le, from_u8, to_u8;
+    read_s16le, write_s16le => i16, from_raw_le, to_raw_le, from_i16, to_i16;
+    read_s16be, write_s16be => i16, from_raw_be, to_raw_be, from_i16, to_i16;
+    read_u16le, write_u16le => u16, from_raw_le, to_raw_le, from_u16, to_u16;
+    read_u16be, write_u16be => u16, from_raw_be, to_raw_be, from_u16, to_u16;
+    read_s24le, write_s24le => i24, from_raw_le, to_raw_le, from_i24, to_i24;
+    read_s24be, write_s24be => i24, from_raw_be, to_raw_be, from_i24, to_i24;
+    read_u24le, write_u24le => u24, from_raw_le, to_raw_le, from_u24, to_u24;
+    read_u24be, write_u24be => u24, from_raw_be, to_raw_be, from_u24, to_u24;
+    read_s32le, write_s32le => i32, from_raw_le, to_raw_le, from_i32, to_i32;
+    read_s32be, write_s32be => i32, from_raw_be, to_raw_be, from_i32, to_i32;
+    read_u32le, write_u32le => u32, from_raw_le, to_raw_le, from_u32, to_u32;
+    read_u32be, write_u32be => u32, from_raw_be, to_raw_be, from_u32, to_u32;
+    read_f32le, write_f32le => f32, from_raw_le, to_raw_le, from_f32, to_f32;
+    read_f32be, write_f32be => f32, from_raw_be, to_raw_be, from_f32, to_f32;
+    read_f64le, write_f64le => f64, from_raw_le, to_raw_le, from_f64, to_f64;
+    read_f64be, write_f64be => f64, from_raw_be, to_raw_be, from_f64, to_f64;
+}
+
+/// Get the function that reads a sample in `format` and converts it to `T`.
+/// Panics if the format is `Format::Invalid`.
+pub(crate) fn read_fn<T: Sample>(format: Format) -> ReadFn<T> {
+    match format {
+        Format::S8 => read_s8::<T>,
+        Format::U8 => read_u8::<T>,
+        Format::S16LE => read_s16le::<T>,
+        Format::S16BE => read_s16be::<T>,
+        Format::U16LE => read_u16le::<T>,
+        Format::U16BE => read_u16be::<T>,
+        Format::S24LE => read_s24le::<T>,
+        Format::S24BE => read_s24be::<T>,
+        Format::U24LE => read_u24le::<T>,
+        Format::U24BE => read_u24be::<T>,
+        Format::S32LE => read_s32le::<T>,
+        Format::S32BE => read_s32be::<T>,
+        Format::U32LE => read_u32le::<T>,
+        Format::U32BE => read_u32be::<T>,
+        Format::Float32LE => read_f32le::<T>,
+        Format::Float32BE => read_f32be::<T>,
+        Format::Float64LE => read_f64le::<T>,
+        Format::Float64BE => read_f64be::<T>,
+        Format::Invalid => panic!("Unknown format"),
+    }
+}
+
+/// Get the function that converts `T` and writes it as a sample in `format`.
+/// Panics if the format is `Format::Invalid`.
+pub(crate) fn write_fn<T: Sample>(format: Format) -> WriteFn<T> {
+    match format {
+        Format::S8 => write_s8::<T>,
+        Format::U8 => write_u8::<T>,
+        Format::S16LE => write_s16le::<T>,
+        Format::S16BE => write_s16be::<T>,
+        Format::U16LE => write_u16le::<T>,
+        Format::U16BE => write_u16be::<T>,
+        Format::S24LE => write_s24le::<T>,
+        Format::S24BE => write_s24be::<T>,
+        Format::U24LE => write_u24le::<T>,
+        Format::U24BE => write_u24be::<T>,
+        Format::S32LE => write_s32le::<T>,
+        Format::S32BE => write_s32be::<T>,
+        Format::U32LE => write_u32le::<T>,
+        Format::U32BE => write_u32be::<T>,
+        Format::Float32LE => write_f32le::<T>,
+        Format::Float32BE => write_f32be::<T>,
+        Format::Float64LE => write_f64le::<T>,
+        Format::Float64BE => write_f64be::<T>,
+        Format::Invalid => panic!("Unknown format"),
+    }
+}
+
+/// An iterator over the frames of an `InStreamReader`, returned by
+/// `InStreamReader::frames()`. Samples are converted to `T`.
+pub struct Frames<'b, T> {
+    areas: &'b [raw::SoundIoChannelArea],
+    frame: usize,
+    frame_count: usize,
+    read: ReadFn<T>,
+}
+
+impl<'b, T: Sample> Frames<'b, T> {
+    /// The caller must ensure that `areas` are valid for `frame_count` frames in `format`.
+    pub(crate) unsafe fn new(
+        areas: &'b [raw::SoundIoChannelArea],
+        format: Format,
+        frame_count: usize,
+    ) -> Frames<'b, T> {
+        Frames {
+            areas,
+            frame: 0,
+            frame_count,
+            read: read_fn::<T>(format),
+        }
+    }
+}
+
+impl<'b, T: Sample> Iterator for Frames<'b, T> {
+    type Item = Frame<'b, T>;
+
+    fn next(&mut self) -> Option<Frame<'b, T>> {
+        if self.frame >= self.frame_count {
+            return None;
+        }
+        let frame = Frame {
+            areas: self.areas,
+            frame: self.frame,
+            read: self.read,
+        };
+        self.frame += 1;
+        Some(frame)
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        let remaining = self.frame_count - self.frame;
+        (remaining, Some(remaining))
+    }
+}
+
+impl<'b, T: Sample> ExactSizeIterator for Frames<'b, T> {}
+
+/// One frame (a sample for each channel) from an `InStreamReader`.
+pub struct Frame<'b, T> {
+    areas: &'b [raw::SoundIoChannelArea],
+    frame: usize,
+    read: ReadFn<T>,
+}
+
+impl<'b, T: Sample> Frame<'b, T> {
+    /// The number of channels in the frame.
+    pub fn channel_count(&self) -> usize {
+        self.areas.len()
+    }
+
+    /// Get the sample for `channel`. Panics if `channel` is out of range.
+    pub fn get(&self, channel: usize) -> T {
+        let area = &self.areas[channel];
+        unsafe { (self.read)(area.ptr.add(self.frame * area.step as usize) as *const u8) }
+    }
+
+    /// Copy the samples of every channel into `dst`, which must have a length of
+    /// `channel_count()`.
+    pub fn read_into(&self, dst: &mut [T]) {
+        assert_eq!(
+            dst.len(),
+            self.areas.len(),
+            "Destination length doesn't match channel count"
+        );
+        for (c, d) in dst.iter_mut().enumerate() {
+            *d = self.get(c);
+        }
+    }
+}
+
+/// An iterator over the frames of an `OutStreamWriter`, returned by
+/// `OutStreamWriter::frames_mut()`. Samples are converted from `T`.
+pub struct FramesMut<'b, T> {
+    areas: &'b [raw::SoundIoChannelArea],
+    frame: usize,
+    frame_count: usize,
+    write: WriteFn<T>,
+}
+
+impl<'b, T: Sample> FramesMut<'b, T> {
+    /// The caller must ensure that `areas` are valid for `frame_count` frames in `format`,
+    /// and not otherwise accessed during `'b`.
+    pub(crate) unsafe fn new(
+        areas: &'b [raw::SoundIoChannelArea],
+        format: Format,
+        frame_count: usize,
+    ) -> FramesMut<'b, T> {
+        FramesMut {
+            areas,
+            frame: 0,
+            frame_count,
+            write: write_fn::<T>(format),
+        }
+    }
+}
+
+impl<'b, T: Sample> Iterator for FramesMut<'b, T> {
+    type Item = FrameMut<'b, T>;
+
+    fn next(&mut self) -> Option<FrameMut<'b, T>> {
+        if self.frame >= self.frame_count {
+            return None;
+        }
+        let frame = FrameMut {
+            areas: self.areas,
+            frame: self.frame,
+            write: self.write,
+        };
+        self.frame += 1;
+        Some(frame)
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        let remaining = self.frame_count - self.frame;
+        (remaining, Some(remaining))
+    }
+}
+
+impl<'b, T: Sample> ExactSizeIterator for FramesMut<'b, T> {}
+
+/// One frame (a sample for each channel) of an `OutStreamWriter`.
+pub struct FrameMut<'b, T> {
+    areas: &'b [raw::SoundIoChannelArea],
+    frame: usize,
+    write: WriteFn<T>,
+}
+
+impl<'b, T: Sample> FrameMut<'b, T> {
+    /// The number of channels in the frame.
+    pub fn channel_count(&self) -> usize {
+        self.areas.len()
+    }
+
+    /// Set the sample for `channel`. Panics if `channel` is out of range.
+    pub fn set(&mut self, channel: usize, sample: T) {
+        let area = &self.areas[channel];
+        unsafe {
+            (self.write)(
+                sample,
+                area.ptr.add(self.frame * area.step as usize) as *mut u8,
+            )
+        }
+    }
+
+    /// Set the samples of every channel from `src`, which must have a length of
+    /// `channel_count()`.
+    pub fn write_from(&mut self, src: &[T])
+    where
+        T: Copy,
+    {
+        assert_eq!(
+            src.len(),
+            self.areas.len(),
+            "Source length doesn't match channel count"
+        );
+        for (c, &s) in src.iter().enumerate() {
+            self.set(c, s);
+        }
+    }
+}
+
+/// An iterator over the samples of one channel of an `InStreamReader`, returned by
+/// `InStreamReader::channel_samples()`. Samples are converted to `T`.
+pub struct ChannelSamples<'b, T> {
+    ptr: *const u8,
+    step: usize,
+    frame: usize,
+    frame_count: usize,
+    read: ReadFn<T>,
+    phantom: PhantomData<&'b ()>,
+}
+
+impl<'b, T: Sample> ChannelSamples<'b, T> {
+    /// The caller must ensure that `area` is valid for `frame_count` frames in `format`.
+    pub(crate) unsafe fn new(
+        area: &raw::SoundIoChannelArea,
+        format: Format,
+        frame_count: usize,
+    ) -> ChannelSamples<'b, T> {
+        ChannelSamples {
+            ptr: area.ptr as *const u8,
+            step: area.step as usize,
+            frame: 0,
+            frame_count,
+            read: read_fn::<T>(format),
+            phantom: PhantomData,
+        }
+    }
+}
+
+impl<'b, T: Sample> Iterator for ChannelSamples<'b, T> {
+    type Item = T;
+
+    fn next(&mut self) -> Option<T> {
+        if self.frame >= self.frame_count {
+            return None;
+        }
+        let sample = unsafe { (self.read)(self.ptr.add(self.frame * self.step)) };
+        self.frame += 1;
+        Some(sample)
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        let remaining = self.frame_count - self.frame;
+        (remaining, Some(remaining))
+    }
+}
+
+impl<'b, T: Sample> ExactSizeIterator for ChannelSamples<'b, T> {}
+
+/// An iterator over the samples of one channel of an `OutStreamWriter`, returned by
+/// `OutStreamWriter::channel_samples_mut()`.
+pub struct ChannelSamplesMut<'b, T> {
+    ptr: *mut u8,
+    step: usize,
+    frame: usize,
+    frame_count: usize,
+    write: WriteFn<T>,
+    phantom: PhantomData<&'b mut ()>,
+}
+
+impl<'b, T: Sample> ChannelSamplesMut<'b, T> {
+    /// The caller must ensure that `area` is valid for `frame_count` frames in `format`,
+    /// and not otherwise accessed during `'b`.
+    pub(crate) unsafe fn new(
+        area: &raw::SoundIoChannelArea,
+        format: Format,
+        frame_count: usize,
+    ) -> ChannelSamplesMut<'b, T> {
+        ChannelSamplesMut {
+            ptr: area.ptr as *mut u8,
+            step: area.step as usize,
+            frame: 0,
+            frame_count,
+            write: write_fn::<T>(format),
+            phantom: PhantomData,
+        }
+    }
+}
+
+impl<'b, T: Sample> Iterator for ChannelSamplesMut<'b, T> {
+    type Item = SampleMut<'b, T>;
+
+    fn next(&mut self) -> Option<SampleMut<'b, T>> {
+        if self.frame >= self.frame_count {
+            return None;
+        }
+        let sample = SampleMut {
+            ptr: unsafe { self.ptr.add(self.frame * self.step) },
+            write: self.write,
+            phantom: PhantomData,
+        };
+        self.frame += 1;
+        Some(sample)
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        let remaining = self.frame_count - self.frame;
+        (remaining, Some(remaining))
+    }
+}
+
+impl<'b, T: Sample> ExactSizeIterator for ChannelSamplesMut<'b, T> {}
+
+/// A single sample in an `OutStreamWriter` that can be set.
+pub struct SampleMut<'b, T> {
+    ptr: *mut u8,
+    write: WriteFn<T>,
+    phantom: PhantomData<&'b mut ()>,
+}
+
+impl<'b, T: Sample> SampleMut<'b, T> {
+    /// Set the sample.
+    pub fn set(self, sample: T) {
+        unsafe { (self.write)(sample, self.ptr) }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn areas(buf: &mut [f32], channels: usize, interleaved: bool) -> Vec<raw::SoundIoChannelArea> {
+        let frames = buf.len() / channels;
+        (0..channels)
+            .map(|c| {
+                if interleaved {
+                    raw::SoundIoChannelArea {
+                        ptr: buf[c..].as_mut_ptr() as *mut _,
+                        step: (channels * 4) as _,
+                    }
+                } else {
+                    raw::SoundIoChannelArea {
+                        ptr: buf[c * frames..].as_mut_ptr() as *mut _,
+                        step: 4,
+                    }
+                }
+            })
+            .collect()
+    }
+
+    #[test]
+    fn interleaved_layout() {
+        let mut buf = vec![0.0f32; 8];
+        let a = areas(&mut buf, 2, true);
+        assert_eq!(
+            interleaved_ptr::<f32>(&a, native::Float32NE),
+            Ok(buf.as_mut_ptr())
+        );
+        assert_eq!(
+            channel_ptr::<f32>(&a, native::Float32NE, 1),
+            Err(SliceError::NotContiguous)
+        );
+    }
+
+    #[test]
+    fn planar_layout() {
+        let mut buf = vec![0.0f32; 8];
+        let a = areas(&mut buf, 2, false);
+        assert_eq!(
+            channel_ptr::<f32>(&a, native::Float32NE, 1),
+            Ok(buf[4..].as_mut_ptr())
+        );
+        assert_eq!(
+            interleaved_ptr::<f32>(&a, native::Float32NE),
+            Err(SliceError::NotInterleaved)
+        );
+    }
+
+    #[test]
+    fn format_mismatch() {
+        let mut buf = vec![0.0f32; 8];
+        let a = areas(&mut buf, 1, false);
+        assert_eq!(
+            channel_ptr::<i32>(&a, native::Float32NE, 0),
+            Err(SliceError::FormatMismatch {
+                stream: native::Float32NE,
+                requested: native::S32NE,
+            })
+        );
+    }
+
+    #[test]
+    fn strided_view() {
+        let mut buf = vec![0.0f32, 1.0, 2.0, 3.0, 4.0, 5.0];
+        let a = areas(&mut buf, 2, true);
+        let (ptr, step) = strided_ptr::<f32>(&a, native::Float32NE, 1).unwrap();
+        let view = unsafe { ChannelView::new(ptr, step, 3) };
+        assert_eq!(view.iter().collect::<Vec<_>>(), vec![1.0, 3.0, 5.0]);
+        assert_eq!(view[2], 5.0);
+        assert_eq!(view.get(3), None);
+
+        let mut dst = [0.0f32; 3];
+        view.copy_to_slice(&mut dst);
+        assert_eq!(dst, [1.0, 3.0, 5.0]);
+    }
+
+    #[test]
+    fn hole() {
+        let a = vec![raw::SoundIoChannelArea {
+            ptr: ptr::null_mut(),
+            step: 4,
+        }];
+        assert_eq!(
+            channel_ptr::<f32>(&a, native::Float32NE, 0),
+            Err(SliceError::Hole)
+        );
+        assert!(strided_ptr::<f32>(&a, native::Float32NE, 0).is_err());
+    }
+
+    #[test]
+    fn frame_iterators_convert() {
+        let mut buf = vec![0i16; 6];
+        let a = areas_i16(&mut buf, 2);
+
+        let frames = unsafe { FramesMut::<f32>::new(&a, native::S16NE, 3) };
+        for (f, mut frame) in frames.enumerate() {
+            frame.write_from(&[f as f32 * 0.25, -1.0]);
+        }
+        assert_eq!(buf, vec![0, -32768, 8192, -32768, 16384, -32768]);
+
+        let frames = unsafe { Frames::<i16>::new(&a, native::S16NE, 3) };
+        let left: Vec<i16> = frames.map(|frame| frame.get(0)).collect();
+        assert_eq!(left, vec![0, 8192, 16384]);
+
+        let right = unsafe { ChannelSamples::<i32>::new(&a[1], native::S16NE, 3) };
+        assert!(right.into_iter().all(|s| s == i32::min_value()));
+
+        let left = unsafe { ChannelSamplesMut::<i16>::new(&a[0], native::S16NE, 3) };
+        for s in left {
+            s.set(7);
+        }
+        assert_eq!(buf, vec![7, -32768, 7, -32768, 7, -32768]);
+    }
+
+    fn areas_i16(buf: &mut [i16], channels: usize) -> Vec<raw::SoundIoChannelArea> {
+        (0..channels)
+            .map(|c| raw::SoundIoChannelArea {
+                ptr: buf[c..].as_mut_ptr() as *mut _,
+                step: (channels * 2) as _,
+            })
+            .collect()
+    }
+}
@@ -0,0 +1,281 @@
+use super::format::*;
+use super::layout::*;
+use super::types::*;
+
+/// A concrete stream configuration that a device supports. It is returned by
+/// `Device::negotiate_config()` and can be passed to `OutStreamBuilder::config()`
+/// or `InStreamBuilder::config()`.
+#[derive(Debug, Clone)]
+pub struct StreamConfig {
+    /// The sample rate in frames per second.
+    pub sample_rate: i32,
+    /// The sample format.
+    pub format: Format,
+    /// The channel layout.
+    pub layout: ChannelLayout,
+}
+
+/// A `Compromise` records a preference passed to `Device::negotiate_config()` that
+/// could not be met exactly. `preferred` is always the first (most preferred) value
+/// that was requested, and `chosen` is the value that is used instead.
+#[derive(Debug, Clone)]
+pub enum Compromise {
+    /// The most preferred format is not supported.
+    Format { preferred: Format, chosen: Format },
+    /// The most preferred sample rate is not supported.
+    SampleRate { preferred: i32, chosen: i32 },
+    /// The most preferred channel layout is not supported.
+    Layout {
+        preferred: ChannelLayout,
+        chosen: ChannelLayout,
+    },
+}
+
+/// The result of `Device::negotiate_config()`: the configuration to use, and a list
+/// of preferences that could not be met. If `compromises` is empty you got exactly
+/// what you asked for first.
+#[derive(Debug, Clone)]
+pub struct NegotiatedConfig {
+    pub config: StreamConfig,
+    pub compromises: Vec<Compromise>,
+}
+
+/// What the device supports. This is separate from `Device` so that the
+/// negotiation logic can be tested without a backend.
+pub(crate) struct Capabilities<'c> {
+    pub formats: &'c [Format],
+    pub sample_rates: &'c [SampleRateRange],
+    pub layouts: &'c [ChannelLayout],
+    pub current_format: Format,
+    pub current_sample_rate: i32,
+    pub current_layout: &'c ChannelLayout,
+}
+
+/// Pick the first preferred value that is supported, falling back to the device's
+/// own settings. See `Device::negotiate_config()`.
+pub(crate) fn negotiate<F>(
+    caps: &Capabilities,
+    formats: &[Format],
+    sample_rates: &[i32],
+    layouts: &[ChannelLayout],
+    nearest_sample_rate: F,
+) -> NegotiatedConfig
+where
+    F: Fn(i32) -> i32,
+{
+    let mut compromises = Vec::new();
+
+    // Format.
+    let format = match formats.iter().find(|f| caps.formats.contains(f)) {
+        Some(&f) => f,
+        None => {
+            if caps.formats.contains(&caps.current_format) {
+                caps.current_format
+            } else {
+                caps.formats.first().cloned().unwrap_or(Format::Invalid)
+            }
+        }
+    };
+    if let Some(&preferred) = formats.first() {
+        if preferred != format {
+            compromises.push(Compromise::Format {
+                preferred,
+                chosen: format,
+            });
+        }
+    }
+
+    // Sample rate.
+    let supported = |rate: i32| {
+        caps.sample_rates
+            .iter()
+            .any(|r| rate >= r.min && rate <= r.max)
+    };
+    let sample_rate = match sample_rates.iter().find(|&&r| supported(r)) {
+        Some(&r) => r,
+        // Nothing matches, so get as close as possible to the first choice.
+        None => match sample_rates.first() {
+            Some(&r) => nearest_sample_rate(r),
+            None => {
+                if caps.current_sample_rate > 0 {
+                    caps.current_sample_rate
+                } else {
+                    caps.sample_rates.first().map(|r| r.max).unwrap_or(0)
+                }
+            }
+        },
+    };
+    if let Some(&preferred) = sample_rates.first() {
+        if preferred != sample_rate {
+            compromises.push(Compromise::SampleRate {
+                preferred,
+                chosen: sample_rate,
+            });
+        }
+    }
+
+    // Layout. Layouts are compared by their channels only; the name is just for display.
+    let supports_layout =
+        |layout: &ChannelLayout| caps.layouts.iter().any(|l| l.channels == layout.channels);
+    let layout = match layouts.iter().find(|l| supports_layout(l)) {
+        Some(l) => l.clone(),
+        None => {
+            // Try to at least keep the channel count of the first choice.
+            let same_count = layouts.first().and_then(|preferred| {
+                caps.layouts
+                    .iter()
+                    .find(|l| l.channels.len() == preferred.channels.len())
+            });
+            match same_count {
+                Some(l) => l.clone(),
+                None => {
+                    if !caps.current_layout.channels.is_empty() {
+                        caps.current_layout.clone()
+                    } else if let Some(l) = caps.layouts.first() {
+                        l.clone()
+                    } else {
+                        // libsoundio guarantees at least one layout, so this is unlikely.
+                        layouts
+                            .first()
+                            .cloned()
+                            .unwrap_or_else(|| ChannelLayout::get_default(2))
+                    }
+                }
+            }
+        }
+    };
+    if let Some(preferred) = layouts.first() {
+        if preferred.channels != layout.channels {
+            compromises.push(Compromise::Layout {
+                preferred: preferred.clone(),
+                chosen: layout.clone(),
+            });
+        }
+    }
+
+    NegotiatedConfig {
+        config: StreamConfig {
+            sample_rate,
+            format,
+            layout,
+        },
+        compromises,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::channels::*;
+    use super::*;
+
+    fn stereo() -> ChannelLayout {
+        ChannelLayout {
+            name: "Stereo".to_string(),
+            channels: vec![ChannelId::FrontLeft, ChannelId::FrontRight],
+        }
+    }
+
+    fn mono() -> ChannelLayout {
+        ChannelLayout {
+            name: "Mono".to_string(),
+            channels: vec![ChannelId::FrontCenter],
+        }
+    }
+
+    fn quad() -> ChannelLayout {
+        ChannelLayout {
+            name: "Quad".to_string(),
+            channels: vec![
+                ChannelId::FrontLeft,
+                ChannelId::FrontRight,
+                ChannelId::BackLeft,
+                ChannelId::BackRight,
+            ],
+        }
+    }
+
+    fn run(
+        formats: &[Format],
+        sample_rates: &[i32],
+        layouts: &[ChannelLayout],
+    ) -> NegotiatedConfig {
+        let dev_formats = [Format::S16LE, Format::S32LE];
+        let dev_rates = [
+            SampleRateRange {
+                min: 44100,
+                max: 44100,
+            },
+            SampleRateRange {
+                min: 48000,
+                max: 96000,
+            },
+        ];
+        let dev_layouts = [stereo(), quad()];
+        let current = stereo();
+        let caps = Capabilities {
+            formats: &dev_formats,
+            sample_rates: &dev_rates,
+            layouts: &dev_layouts,
+            current_format: Format::S32LE,
+            current_sample_rate: 48000,
+            current_layout: &current,
+        };
+        let nearest = |rate: i32| {
+            if rate <= 44100 {
+                44100
+            } else if rate < 48000 {
+                48000
+            } else {
+                96000
+            }
+        };
+        negotiate(&caps, formats, sample_rates, layouts, nearest)
+    }
+
+    #[test]
+    fn first_choices_supported() {
+        let n = run(&[Format::S16LE], &[44100], &[stereo()]);
+        assert_eq!(n.config.format, Format::S16LE);
+        assert_eq!(n.config.sample_rate, 44100);
+        assert_eq!(n.config.layout.channels, stereo().channels);
+        assert!(n.compromises.is_empty());
+    }
+
+    #[test]
+    fn later_choices_are_compromises() {
+        let n = run(
+            &[Format::Float32LE, Format::S32LE],
+            &[22050, 64000],
+            &[mono(), quad()],
+        );
+        assert_eq!(n.config.format, Format::S32LE);
+        assert_eq!(n.config.sample_rate, 64000);
+        assert_eq!(n.config.layout.channels, quad().channels);
+        assert_eq!(n.compromises.len(), 3);
+        match n.compromises[0] {
+            Compromise::Format { preferred, chosen } => {
+                assert_eq!(preferred, Format::Float32LE);
+                assert_eq!(chosen, Format::S32LE);
+            }
+            _ => panic!("Expected format compromise"),
+        }
+    }
+
+    #[test]
+    fn nothing_supported_falls_back() {
+        let n = run(&[Format::Float64BE], &[22050, 32000], &[mono()]);
+        assert_eq!(n.config.format, Format::S32LE);
+        assert_eq!(n.config.sample_rate, 44100);
+        assert_eq!(n.config.layout.channels, stereo().channels);
+        assert_eq!(n.compromises.len(), 3);
+    }
+
+    #[test]
+    fn no_preferences_uses_current() {
+        let n = run(&[], &[], &[]);
+        assert_eq!(n.config.format, Format::S32LE);
+        assert_eq!(n.config.sample_rate, 48000);
+        assert_eq!(n.config.layout.channels, stereo().channels);
+        assert!(n.compromises.is_empty());
+    }
+}
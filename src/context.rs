@@ -3,9 +3,14 @@ extern crate libsoundio_sys as raw;
 use super::backend::*;
 use super::device::*;
 use super::error::*;
+use super::events::*;
+use super::types::*;
 
 use std::marker::PhantomData;
 use std::os::raw::{c_char, c_int};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Condvar, Mutex, MutexGuard};
+use std::thread;
 
 /// `Context` represents the libsoundio library context.
 ///
@@ -29,6 +34,10 @@ pub struct Context<'a> {
     /// The optional callbacks. They are boxed so that we can take a raw pointer
     /// to the heap object and give use it as `void* userdata`.
     userdata: Box<ContextUserData<'a>>,
+    /// Held while the device list is used or replaced. See `Context::start_event_thread()`.
+    device_lock: Arc<Mutex<()>>,
+    /// The thread started by `Context::start_event_thread()`.
+    event_thread: Option<thread::JoinHandle<()>>,
 }
 
 // The callbacks required for a context are stored in this object.
@@ -36,6 +45,56 @@ pub struct ContextUserData<'a> {
     backend_disconnect_callback: Option<Box<dyn FnMut(Error) + 'a>>,
     devices_change_callback: Option<Box<dyn FnMut() + 'a>>,
     events_signal_callback: Option<Box<dyn FnMut() + 'a>>,
+    event_sink: Arc<EventSink>,
+    events_signal: Arc<EventsSignal>,
+}
+
+// Wakes up the event thread. It is notified by `on_events_signal`, which libsoundio calls
+// when there are events to flush, and when the thread is stopped.
+struct EventsSignal {
+    signaled: Mutex<bool>,
+    cond: Condvar,
+    shutdown: AtomicBool,
+}
+
+impl EventsSignal {
+    fn new() -> EventsSignal {
+        EventsSignal {
+            signaled: Mutex::new(false),
+            cond: Condvar::new(),
+            shutdown: AtomicBool::new(false),
+        }
+    }
+
+    fn notify(&self) {
+        *self.signaled.lock().unwrap_or_else(|e| e.into_inner()) = true;
+        self.cond.notify_all();
+    }
+
+    // Block until notified. Returns false if the thread should stop.
+    fn wait(&self) -> bool {
+        let mut signaled = self.signaled.lock().unwrap_or_else(|e| e.into_inner());
+        while !*signaled {
+            signaled = self.cond.wait(signaled).unwrap_or_else(|e| e.into_inner());
+        }
+        *signaled = false;
+        !self.shutdown.load(Ordering::Acquire)
+    }
+
+    fn set_shutdown(&self, shutdown: bool) {
+        self.shutdown.store(shutdown, Ordering::Release);
+    }
+}
+
+// The raw context, which is only used by the event thread while the `Context` waits for it
+// to finish.
+struct SoundIoPtr(*mut raw::SoundIo);
+
+unsafe impl Send for SoundIoPtr {}
+
+/// Lock the device list. A panic while it is locked can't leave it in a bad state.
+pub(crate) fn lock_devices(lock: &Mutex<()>) -> MutexGuard<'_, ()> {
+    lock.lock().unwrap_or_else(|e| e.into_inner())
 }
 
 // See `Context::new_with_callbacks()`.
@@ -46,6 +105,7 @@ extern "C" fn on_backend_disconnect(sio: *mut raw::SoundIo, err: c_int) {
     let raw_userdata_pointer = unsafe { (*sio).userdata as *mut ContextUserData };
     let userdata = unsafe { &mut (*raw_userdata_pointer) };
 
+    userdata.event_sink.record(Event::BackendDisconnected(err));
     if let Some(ref mut cb) = userdata.backend_disconnect_callback {
         cb(err);
     } else {
@@ -60,10 +120,9 @@ extern "C" fn on_devices_change(sio: *mut raw::SoundIo) {
     let raw_userdata_pointer = unsafe { (*sio).userdata as *mut ContextUserData };
     let userdata = unsafe { &mut (*raw_userdata_pointer) };
 
+    userdata.event_sink.record(Event::DevicesChanged);
     if let Some(ref mut cb) = userdata.devices_change_callback {
         cb();
-    } else {
-        println!("Devices changed");
     }
 }
 
@@ -73,6 +132,7 @@ extern "C" fn on_events_signal(sio: *mut raw::SoundIo) {
     let raw_userdata_pointer = unsafe { (*sio).userdata as *mut ContextUserData };
     let userdata = unsafe { &mut (*raw_userdata_pointer) };
 
+    userdata.events_signal.notify();
     if let Some(ref mut cb) = userdata.events_signal_callback {
         cb();
     }
@@ -118,7 +178,11 @@ impl<'a> Context<'a> {
                 backend_disconnect_callback: None,
                 devices_change_callback: None,
                 events_signal_callback: None,
+                event_sink: Arc::new(EventSink::new()),
+                events_signal: Arc::new(EventsSignal::new()),
             }),
+            device_lock: Arc::new(Mutex::new(())),
+            event_thread: None,
         };
 
         // Note that libsoundio's default on_backend_disconnect() handler panics!
@@ -154,7 +218,8 @@ impl<'a> Context<'a> {
     /// If you do not supply a callback, the default will panic
     /// with an error message. This callback is also called when the thread
     /// that retrieves device information runs into an unrecoverable condition
-    /// such as running out of memory.
+    /// such as running out of memory. Disconnects are also recorded in the
+    /// context's `EventSink` as `Event::BackendDisconnected`.
     ///
     /// The possible errors passed to the callback are:
     ///
@@ -166,8 +231,8 @@ impl<'a> Context<'a> {
     /// ## `devices_change_callback`
     ///
     /// This is called when the list of devices change. It is only called during a call
-    /// to `Context::flush_events()` or `Context::`wait_events()`. The default behaviour is
-    /// to print "Devices changed" to the console, which you can disable by using an empty callback.
+    /// to `Context::flush_events()` or `Context::`wait_events()`. Changes are also
+    /// recorded in the context's `EventSink` as `Event::DevicesChanged`.
     ///
     /// ## `events_signal_callback`
     ///
@@ -241,6 +306,28 @@ impl<'a> Context<'a> {
         self.app_name.clone()
     }
 
+    /// Set the sink that device change events are recorded in. By default each context
+    /// has its own sink that only counts events. See `EventSink`.
+    ///
+    /// ```
+    /// use std::sync::Arc;
+    ///
+    /// let sink = Arc::new(soundio::EventSink::with_queue(16));
+    /// let mut ctx = soundio::Context::new();
+    /// ctx.set_event_sink(sink.clone());
+    /// assert_eq!(sink.devices_change_count(), 0);
+    /// ```
+    pub fn set_event_sink(&mut self, event_sink: Arc<EventSink>) {
+        // The event thread uses the sink while the device list is locked.
+        let _lock = lock_devices(&self.device_lock);
+        self.userdata.event_sink = event_sink;
+    }
+
+    /// The sink that device change events are recorded in.
+    pub fn event_sink(&self) -> &Arc<EventSink> {
+        &self.userdata.event_sink
+    }
+
     /// Connect to the default backend, trying them in the order returned by `available_backends()`.
     /// It will fail with `Error::Invalid` if this instance is already connected to a backend.
     ///
@@ -322,6 +409,8 @@ impl<'a> Context<'a> {
     /// ctx.disconnect();
     /// ```
     pub fn disconnect(&mut self) {
+        self.stop_event_thread();
+        let _lock = self.lock_devices();
         unsafe {
             raw::soundio_disconnect(self.soundio);
         }
@@ -376,11 +465,13 @@ impl<'a> Context<'a> {
     ///
     /// This must be called from the same thread as the thread in which you call
     /// any function that gets an input or output device, count or index (e.g.
-    /// `Context::default_input_device_index()`).
+    /// `Context::default_input_device_index()`), unless the event thread is running.
+    /// See `Context::start_event_thread()`.
     ///
     /// Note that if you do not care about learning about updated devices, you
     /// can call this function only once ever and never call `Context::wait_events()`.
     pub fn flush_events(&self) {
+        let _lock = self.lock_devices();
         unsafe {
             raw::soundio_flush_events(self.soundio);
         }
@@ -388,26 +479,197 @@ impl<'a> Context<'a> {
 
     /// This function calls `Context::flush_events()` then blocks until another event
     /// is ready or you call `wakeup`. Be ready for spurious wakeups.
+    ///
+    /// Panics if the event thread is running, since it is the one waiting for events.
     pub fn wait_events(&self) {
+        assert!(
+            self.event_thread.is_none(),
+            "wait_events() can't be used while the event thread is running"
+        );
         unsafe {
             raw::soundio_wait_events(self.soundio);
         }
     }
 
+    /// Start a thread that waits for events and calls `flush_events()`, so that you
+    /// don't need to call `flush_events()` or `wait_events()` yourself. The callbacks
+    /// are called on that thread: `devices_change_callback` when the list of devices
+    /// changes and `backend_disconnect_callback` when the backend disconnects (see
+    /// `Context::new_with_callbacks()`). The callbacks must not use the `Context`.
+    ///
+    /// Unlike `Context::new_with_callbacks()`, a backend disconnect doesn't panic if
+    /// `backend_disconnect_callback` is `None`. It is only recorded as
+    /// `Event::BackendDisconnected` in the context's `EventSink`.
+    ///
+    /// The device list is locked while the event thread updates it, so the thread that
+    /// owns the `Context` can keep calling functions like `Context::output_devices()`,
+    /// and opening and dropping streams, while the event thread flushes events.
+    ///
+    /// The thread is stopped by `stop_event_thread()`, `disconnect()` or dropping the
+    /// `Context`.
+    ///
+    /// # Errors
+    ///
+    /// * `Error::Invalid`
+    ///   - the context isn't connected to a backend
+    ///   - the event thread is already running
+    ///   - the context was created by `Context::new_with_callbacks()` with a backend
+    ///     disconnect or devices change callback, since they may not be `Send`
+    /// * `Error::SystemResources` - the thread could not be started.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # fn foo() -> Result<(), soundio::Error> {
+    /// let mut ctx = soundio::Context::new();
+    /// ctx.connect_backend(soundio::Backend::Dummy)?;
+    /// ctx.start_event_thread(
+    ///     Some(|| println!("Devices changed")),
+    ///     Some(|err| println!("Backend disconnected: {}", err)),
+    /// )?;
+    ///
+    /// // The event thread has already flushed the events once.
+    /// let dev = ctx.default_output_device()?;
+    /// println!("Default output device: {}", dev.name());
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn start_event_thread<DevicesChangeCB, BackendDisconnectCB>(
+        &mut self,
+        devices_change_callback: Option<DevicesChangeCB>,
+        backend_disconnect_callback: Option<BackendDisconnectCB>,
+    ) -> Result<()>
+    where
+        DevicesChangeCB: 'static + Send + FnMut(),
+        BackendDisconnectCB: 'static + Send + FnMut(Error),
+    {
+        if self.current_backend() == Backend::None
+            || self.event_thread.is_some()
+            || self.userdata.devices_change_callback.is_some()
+            || self.userdata.backend_disconnect_callback.is_some()
+        {
+            return Err(Error::Invalid);
+        }
+
+        // Flush once before returning so that the devices can be used straight away. The
+        // callbacks are set afterwards so that they are only called on the event thread.
+        self.flush_events();
+
+        if let Some(cb) = devices_change_callback {
+            self.userdata.devices_change_callback = Some(Box::new(cb));
+        }
+        // Without a callback `on_backend_disconnect()` panics, which would abort the
+        // process from the event thread. The disconnect is still recorded in the
+        // `EventSink`.
+        self.userdata.backend_disconnect_callback = match backend_disconnect_callback {
+            Some(cb) => Some(Box::new(cb)),
+            None => Some(Box::new(|_| {})),
+        };
+
+        let soundio = SoundIoPtr(self.soundio);
+        let device_lock = self.device_lock.clone();
+        let signal = self.userdata.events_signal.clone();
+        signal.set_shutdown(false);
+
+        let thread = thread::Builder::new()
+            .name("soundio events".to_string())
+            .spawn(move || {
+                let soundio = soundio;
+                while signal.wait() {
+                    let _lock = lock_devices(&device_lock);
+                    unsafe {
+                        raw::soundio_flush_events(soundio.0);
+                    }
+                }
+            });
+        match thread {
+            Ok(thread) => {
+                self.event_thread = Some(thread);
+                Ok(())
+            }
+            Err(_) => {
+                self.clear_event_thread_callbacks();
+                Err(Error::SystemResources)
+            }
+        }
+    }
+
+    /// Stop the thread started by `start_event_thread()` and wait for it to finish. This
+    /// does nothing if it isn't running. The callbacks that were passed to
+    /// `start_event_thread()` are dropped.
+    pub fn stop_event_thread(&mut self) {
+        if let Some(thread) = self.event_thread.take() {
+            // `soundio_wakeup()` only wakes up `soundio_wait_events()`, so the thread waits
+            // for `EventsSignal` instead.
+            self.userdata.events_signal.set_shutdown(true);
+            self.userdata.events_signal.notify();
+            let _ = thread.join();
+            self.clear_event_thread_callbacks();
+        }
+    }
+
+    /// Returns true if the thread started by `start_event_thread()` is running.
+    pub fn is_event_thread_running(&self) -> bool {
+        self.event_thread.is_some()
+    }
+
+    fn clear_event_thread_callbacks(&mut self) {
+        self.userdata.devices_change_callback = None;
+        self.userdata.backend_disconnect_callback = None;
+    }
+
     /// Wake up any other threads currently blocking in `Context::wait_events()`.
     ///
-    /// In order to enable this functionality, `Context` implements the `Send` and
-    /// `Sync` traits despite the fact that not all functions can be called from all threads.
-    /// Be careful.
+    /// `Context` can't be shared between threads, so to call this from another thread
+    /// use a `ContextWaker` from `Context::waker()`. `Context::start_event_thread()` is
+    /// an easier way to handle events.
     pub fn wakeup(&self) {
-        // To do this properly it might be necessary to split Context into multiple objects, one for
-        // each thread, or maybe one that is Send/Sync and another that isn't. That is rather
-        // complicated however and there is probably a better way.
         unsafe {
             raw::soundio_wakeup(self.soundio);
         }
     }
 
+    /// The raw context.
+    pub(crate) fn raw(&self) -> *mut raw::SoundIo {
+        self.soundio
+    }
+
+    /// Get a handle that can call `wakeup()` from any thread. It borrows the `Context`,
+    /// so it can't outlive it.
+    ///
+    /// # Errors
+    ///
+    /// * `Error::Invalid` - the context isn't connected to a backend, so there is
+    ///   nothing to wake up.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// extern crate crossbeam;
+    /// extern crate soundio;
+    ///
+    /// # fn foo() -> Result<(), soundio::Error> {
+    /// let mut ctx = soundio::Context::new();
+    /// ctx.connect()?;
+    ///
+    /// let waker = ctx.waker()?;
+    /// crossbeam::scope(|scope| {
+    ///     scope.spawn(move || waker.wakeup());
+    ///     ctx.wait_events();
+    /// });
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn waker(&self) -> Result<ContextWaker<'_>> {
+        if self.current_backend() == Backend::None {
+            return Err(Error::Invalid);
+        }
+        Ok(ContextWaker {
+            soundio: self.soundio,
+            phantom: PhantomData,
+        })
+    }
+
     /// If necessary you can manually trigger a device rescan. Normally you will
     /// not ever have to call this function, as libsoundio listens to system events
     /// for device changes and responds to them by rescanning devices and preparing
@@ -447,21 +709,8 @@ impl<'a> Context<'a> {
     /// }
     /// ```
     pub fn input_device(&self, index: usize) -> Result<Device> {
-        let device = unsafe { raw::soundio_get_input_device(self.soundio, index as c_int) };
-        if device.is_null() {
-            return Err(Error::OpeningDevice);
-        }
-
-        let probe_error = unsafe { (*device).probe_error };
-
-        if probe_error != 0 {
-            return Err(probe_error.into());
-        }
-
-        Ok(Device {
-            device,
-            phantom: PhantomData,
-        })
+        let _lock = self.lock_devices();
+        self.get_device(DeviceAim::Input, index)
     }
 
     /// Use this function to retrieve an output device given its index. Before getting devices
@@ -485,45 +734,24 @@ impl<'a> Context<'a> {
     /// }
     /// ```
     pub fn output_device(&self, index: usize) -> Result<Device> {
-        let device = unsafe { raw::soundio_get_output_device(self.soundio, index as c_int) };
-        if device.is_null() {
-            return Err(Error::OpeningDevice);
-        }
-
-        let probe_error = unsafe { (*device).probe_error };
-
-        if probe_error != 0 {
-            return Err(probe_error.into());
-        }
-
-        Ok(Device {
-            device,
-            phantom: PhantomData,
-        })
+        let _lock = self.lock_devices();
+        self.get_device(DeviceAim::Output, index)
     }
 
     /// Get the number of input devices in this machine. You *must* call
     /// `Context::flush_events()` at least once before calling this function
     /// otherwise it will panic!
     pub fn input_device_count(&self) -> usize {
-        let count = unsafe { raw::soundio_input_device_count(self.soundio) };
-        assert!(
-            count != -1,
-            "flush_events() must be called before input_device_count()"
-        );
-        count as _
+        let _lock = self.lock_devices();
+        self.device_count(DeviceAim::Input)
     }
 
     /// Get the number of output devices in this machine. You *must* call
     /// `Context::flush_events()` at least once before calling this function
     /// otherwise it will panic!
     pub fn output_device_count(&self) -> usize {
-        let count = unsafe { raw::soundio_output_device_count(self.soundio) };
-        assert!(
-            count != -1,
-            "flush_events() must be called before output_device_count()"
-        );
-        count as _
+        let _lock = self.lock_devices();
+        self.device_count(DeviceAim::Output)
     }
 
     /// Returns the index of the default input device. You must call
@@ -547,11 +775,8 @@ impl<'a> Context<'a> {
     /// }
     /// ```
     pub fn default_input_device_index(&self) -> Option<usize> {
-        let index = unsafe { raw::soundio_default_input_device_index(self.soundio) };
-        match index {
-            -1 => None,
-            _ => Some(index as usize),
-        }
+        let _lock = self.lock_devices();
+        self.default_device_index(DeviceAim::Input)
     }
 
     /// Returns the index of the default output device. You must call
@@ -575,11 +800,8 @@ impl<'a> Context<'a> {
     /// }
     /// ```
     pub fn default_output_device_index(&self) -> Option<usize> {
-        let index = unsafe { raw::soundio_default_output_device_index(self.soundio) };
-        match index {
-            -1 => None,
-            _ => Some(index as usize),
-        }
+        let _lock = self.lock_devices();
+        self.default_device_index(DeviceAim::Output)
     }
 
     /// Get all the input devices as a vector. You *must* call `Context::flush_events()`
@@ -599,12 +821,7 @@ impl<'a> Context<'a> {
     /// }
     /// ```
     pub fn input_devices(&self) -> Result<Vec<Device>> {
-        let count = self.input_device_count();
-        let mut devices = Vec::new();
-        for i in 0..count {
-            devices.push(self.input_device(i)?);
-        }
-        Ok(devices)
+        self.devices(DeviceAim::Input)
     }
 
     /// Get all the output devices as a vector. You *must* call `Context::flush_events()`
@@ -624,12 +841,7 @@ impl<'a> Context<'a> {
     /// }
     /// ```
     pub fn output_devices(&self) -> Result<Vec<Device>> {
-        let count = self.output_device_count();
-        let mut devices = Vec::new();
-        for i in 0..count {
-            devices.push(self.output_device(i)?);
-        }
-        Ok(devices)
+        self.devices(DeviceAim::Output)
     }
 
     /// Get the default input device. You *must* call `Context::flush_events()`
@@ -648,11 +860,12 @@ impl<'a> Context<'a> {
     /// println!("The default input device is {}", dev.name());
     /// ```
     pub fn default_input_device(&self) -> Result<Device> {
-        let index = match self.default_input_device_index() {
+        let _lock = self.lock_devices();
+        let index = match self.default_device_index(DeviceAim::Input) {
             Some(x) => x,
             None => return Err(Error::NoSuchDevice),
         };
-        self.input_device(index)
+        self.get_device(DeviceAim::Input, index)
     }
 
     /// Get the default output device. You *must* call `Context::flush_events()`
@@ -671,16 +884,93 @@ impl<'a> Context<'a> {
     /// println!("The default output device is {}", dev.name());
     /// ```
     pub fn default_output_device(&self) -> Result<Device> {
-        let index = match self.default_output_device_index() {
+        let _lock = self.lock_devices();
+        let index = match self.default_device_index(DeviceAim::Output) {
             Some(x) => x,
             None => return Err(Error::NoSuchDevice),
         };
-        self.output_device(index)
+        self.get_device(DeviceAim::Output, index)
+    }
+
+    // Lock the device list so that the event thread can't replace it while it is used.
+    fn lock_devices(&self) -> MutexGuard<'_, ()> {
+        lock_devices(&self.device_lock)
+    }
+
+    fn devices(&self, aim: DeviceAim) -> Result<Vec<Device<'_>>> {
+        let mut devices = Vec::new();
+        // Dropping a device locks the device list, so on error the devices that were
+        // already got must be dropped after it is unlocked.
+        let result = {
+            let _lock = self.lock_devices();
+            (0..self.device_count(aim)).try_for_each(|i| {
+                devices.push(self.get_device(aim, i)?);
+                Ok(())
+            })
+        };
+        result.map(|()| devices)
+    }
+
+    // The following functions use the device list, which must be locked.
+
+    fn get_device(&self, aim: DeviceAim, index: usize) -> Result<Device<'_>> {
+        let device = unsafe {
+            match aim {
+                DeviceAim::Input => raw::soundio_get_input_device(self.soundio, index as c_int),
+                DeviceAim::Output => raw::soundio_get_output_device(self.soundio, index as c_int),
+            }
+        };
+        if device.is_null() {
+            return Err(Error::OpeningDevice);
+        }
+
+        let probe_error = unsafe { (*device).probe_error };
+
+        if probe_error != 0 {
+            unsafe {
+                raw::soundio_device_unref(device);
+            }
+            return Err(probe_error.into());
+        }
+
+        Ok(Device {
+            device,
+            device_lock: self.device_lock.clone(),
+            phantom: PhantomData,
+        })
+    }
+
+    fn device_count(&self, aim: DeviceAim) -> usize {
+        let count = unsafe {
+            match aim {
+                DeviceAim::Input => raw::soundio_input_device_count(self.soundio),
+                DeviceAim::Output => raw::soundio_output_device_count(self.soundio),
+            }
+        };
+        assert!(
+            count != -1,
+            "flush_events() must be called before getting the device count"
+        );
+        count as _
+    }
+
+    fn default_device_index(&self, aim: DeviceAim) -> Option<usize> {
+        let index = unsafe {
+            match aim {
+                DeviceAim::Input => raw::soundio_default_input_device_index(self.soundio),
+                DeviceAim::Output => raw::soundio_default_output_device_index(self.soundio),
+            }
+        };
+        match index {
+            -1 => None,
+            _ => Some(index as usize),
+        }
     }
 }
 
 impl<'a> Drop for Context<'a> {
     fn drop(&mut self) {
+        self.stop_event_thread();
         unsafe {
             // This also disconnects if necessary.
             raw::soundio_destroy(self.soundio);
@@ -688,10 +978,33 @@ impl<'a> Drop for Context<'a> {
     }
 }
 
-// This allows wakeup and wait_events to be called from other threads.
+// A context can be used from any thread, but only from one at a time. Use `ContextWaker`
+// to wake it up from other threads.
 // TODO: Find out exactly the thread-safety properties of libsoundio.
 unsafe impl<'a> Send for Context<'a> {}
-unsafe impl<'a> Sync for Context<'a> {}
+
+/// A handle that wakes up `Context::wait_events()` from any thread. It is obtained
+/// with `Context::waker()` and can be cloned and sent to other threads, but it can't
+/// outlive the `Context`.
+#[derive(Clone)]
+pub struct ContextWaker<'a> {
+    soundio: *mut raw::SoundIo,
+    phantom: PhantomData<&'a ()>,
+}
+
+impl<'a> ContextWaker<'a> {
+    /// Wake up any threads currently blocking in `Context::wait_events()`. See
+    /// `Context::wakeup()`.
+    pub fn wakeup(&self) {
+        unsafe {
+            raw::soundio_wakeup(self.soundio);
+        }
+    }
+}
+
+// soundio_wakeup() can be called from any thread, and the borrow keeps the context alive.
+unsafe impl<'a> Send for ContextWaker<'a> {}
+unsafe impl<'a> Sync for ContextWaker<'a> {}
 
 #[cfg(test)]
 mod tests {
@@ -713,5 +1026,57 @@ mod tests {
         println!("Available backends: {:?}", ctx.available_backends());
     }
 
+    #[test]
+    fn events_signal() {
+        let signal = Arc::new(EventsSignal::new());
+        let other = signal.clone();
+        let waiter = thread::spawn(move || {
+            let mut wakeups = 0;
+            while other.wait() {
+                wakeups += 1;
+            }
+            wakeups
+        });
+        // Notifications before the thread waits are not lost.
+        signal.notify();
+        while *signal.signaled.lock().unwrap() {
+            thread::yield_now();
+        }
+        signal.set_shutdown(true);
+        signal.notify();
+        assert_eq!(waiter.join().unwrap(), 1);
+    }
+
+    #[test]
+    fn waker_is_send_sync() {
+        fn check<T: Send + Sync + Clone>() {}
+        check::<ContextWaker>();
+
+        let mut ctx = Context::new();
+        match ctx.waker() {
+            Err(Error::Invalid) => {}
+            r => panic!("Unexpected {:?}", r.map(|_| ())),
+        }
+
+        ctx.connect_backend(Backend::Dummy).unwrap();
+        let waker = ctx.waker().unwrap();
+        thread::scope(|scope| {
+            let other = waker.clone();
+            scope.spawn(move || other.wakeup());
+        });
+        waker.wakeup();
+    }
+
+    #[test]
+    fn event_thread_needs_backend() {
+        let mut ctx = Context::new();
+        match ctx.start_event_thread(Some(|| {}), None::<fn(Error)>) {
+            Err(Error::Invalid) => {}
+            r => panic!("Unexpected {:?}", r),
+        }
+        assert!(!ctx.is_event_thread_running());
+        ctx.stop_event_thread();
+    }
+
     // TODO: More tests.
 }
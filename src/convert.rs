@@ -0,0 +1,456 @@
+extern crate libsoundio_sys as raw;
+
+use super::dither::*;
+use super::error::*;
+use super::format::*;
+use super::sample::*;
+
+use std::cmp::min;
+
+// Run `$body` with `$enc` set to the `Encoding` for `$format`, which must not be invalid.
+macro_rules! with_encoding {
+    ($format:expr, $enc:ident => $body:expr) => {
+        match $format {
+            Format::S8 => {
+                type $enc = encoding::S8;
+                $body
+            }
+            Format::U8 => {
+                type $enc = encoding::U8;
+                $body
+            }
+            Format::S16LE => {
+                type $enc = encoding::S16LE;
+                $body
+            }
+            Format::S16BE => {
+                type $enc = encoding::S16BE;
+                $body
+            }
+            Format::U16LE => {
+                type $enc = encoding::U16LE;
+                $body
+            }
+            Format::U16BE => {
+                type $enc = encoding::U16BE;
+                $body
+            }
+            Format::S24LE => {
+                type $enc = encoding::S24LE;
+                $body
+            }
+            Format::S24BE => {
+                type $enc = encoding::S24BE;
+                $body
+            }
+            Format::U24LE => {
+                type $enc = encoding::U24LE;
+                $body
+            }
+            Format::U24BE => {
+                type $enc = encoding::U24BE;
+                $body
+            }
+            Format::S32LE => {
+                type $enc = encoding::S32LE;
+                $body
+            }
+            Format::S32BE => {
+                type $enc = encoding::S32BE;
+                $body
+            }
+            Format::U32LE => {
+                type $enc = encoding::U32LE;
+                $body
+            }
+            Format::U32BE => {
+                type $enc = encoding::U32BE;
+                $body
+            }
+            Format::Float32LE => {
+                type $enc = encoding::Float32LE;
+                $body
+            }
+            Format::Float32BE => {
+                type $enc = encoding::Float32BE;
+                $body
+            }
+            Format::Float64LE => {
+                type $enc = encoding::Float64LE;
+                $body
+            }
+            Format::Float64BE => {
+                type $enc = encoding::Float64BE;
+                $body
+            }
+            Format::Invalid => unreachable!(),
+        }
+    };
+}
+
+/// Convert a buffer of samples in one format to another format. `src` contains raw
+/// samples in `src_format` and `dst` receives them in `dst_format`. Any of the
+/// formats apart from `Format::Invalid` can be used, including the 24-bit ones, which
+/// are stored in the low three bytes of a 32-bit word as in libsoundio.
+///
+/// The conversion is the same as the `Sample` trait's. The inner loop is specialised
+/// for every pair of formats, and nothing is allocated, so it is fast enough to be used
+/// in a read or write callback.
+///
+/// If the buffers are different sizes as many samples are converted as fit in both,
+/// and any trailing bytes that don't make up a whole sample are ignored. It returns
+/// the number of samples converted.
+///
+/// # Errors
+///
+/// * `Error::Invalid` - either format is `Format::Invalid`.
+///
+/// # Examples
+///
+/// ```
+/// let src = [0x00u8, 0x40, 0x00, 0xC0]; // 0.5 and -0.5 as S16LE.
+/// let mut dst = [0u8; 8];
+/// let n = soundio::convert_samples(&src, soundio::Format::S16LE, &mut dst, soundio::Format::Float32BE).unwrap();
+/// assert_eq!(n, 2);
+/// assert_eq!(dst, [0x3F, 0x00, 0x00, 0x00, 0xBF, 0x00, 0x00, 0x00]);
+/// ```
+pub fn convert_samples(
+    src: &[u8],
+    src_format: Format,
+    dst: &mut [u8],
+    dst_format: Format,
+) -> Result<usize> {
+    if src_format == Format::Invalid || dst_format == Format::Invalid {
+        return Err(Error::Invalid);
+    }
+
+    if src_format == dst_format {
+        let size = src_format.bytes_per_sample();
+        let count = min(src.len(), dst.len()) / size;
+        dst[..count * size].copy_from_slice(&src[..count * size]);
+        return Ok(count);
+    }
+
+    Ok(with_encoding!(src_format, S => convert_from::<S>(src, dst, dst_format)))
+}
+
+/// Select the destination encoding. This is split from `convert_samples()` so that the
+/// macro expansion is 18 + 18 * 18 arms rather than 18 * 18 nested ones.
+fn convert_from<S: Encoding>(src: &[u8], dst: &mut [u8], dst_format: Format) -> usize {
+    with_encoding!(dst_format, D => convert_pair::<S, D>(src, dst))
+}
+
+/// The inner loop, monomorphised for each pair of formats.
+fn convert_pair<S: Encoding, D: Encoding>(src: &[u8], dst: &mut [u8]) -> usize {
+    let count = min(src.len() / S::SIZE, dst.len() / D::SIZE);
+    for (s, d) in src.chunks_exact(S::SIZE).zip(dst.chunks_exact_mut(D::SIZE)) {
+        D::encode(S::decode(s).convert::<D::Value>(), d);
+    }
+    count
+}
+
+/// Like `convert_samples()` but adds dither (see `Ditherer`) when reducing the bit depth
+/// to an integer format. `src` and `dst` contain interleaved samples for `channel_count`
+/// channels so that noise shaping is done per channel.
+///
+/// If no precision is lost, for example when converting to a float format or from a
+/// smaller integer format, this is the same as `convert_samples()`.
+///
+/// # Errors
+///
+/// * `Error::Invalid`
+///   - either format is `Format::Invalid`
+///   - `channel_count` is 0 or greater than `SOUNDIO_MAX_CHANNELS` (24).
+///
+/// # Examples
+///
+/// ```
+/// let src: Vec<u8> = [0.1f32, -0.1].iter().flat_map(|x| x.to_le_bytes().to_vec()).collect();
+/// let mut dst = [0u8; 4];
+/// let mut ditherer = soundio::Ditherer::new(soundio::DitherKind::Triangular);
+/// soundio::convert_samples_dithered(
+///     &src,
+///     soundio::Format::Float32LE,
+///     &mut dst,
+///     soundio::Format::S16LE,
+///     2,
+///     &mut ditherer,
+/// ).unwrap();
+/// ```
+pub fn convert_samples_dithered(
+    src: &[u8],
+    src_format: Format,
+    dst: &mut [u8],
+    dst_format: Format,
+    channel_count: usize,
+    ditherer: &mut Ditherer,
+) -> Result<usize> {
+    if src_format == Format::Invalid
+        || dst_format == Format::Invalid
+        || channel_count == 0
+        || channel_count > raw::SOUNDIO_MAX_CHANNELS
+    {
+        return Err(Error::Invalid);
+    }
+
+    let scale = match (dither_scale(src_format), dither_scale(dst_format)) {
+        (_, None) => return convert_samples(src, src_format, dst, dst_format),
+        (Some(src_scale), Some(dst_scale)) if src_scale <= dst_scale => {
+            return convert_samples(src, src_format, dst, dst_format)
+        }
+        (_, Some(dst_scale)) => dst_scale,
+    };
+
+    let mut dither = DitherArgs {
+        ditherer,
+        channel_count,
+        scale,
+    };
+    Ok(
+        with_encoding!(src_format, S => convert_from_dithered::<S>(src, dst, dst_format, &mut dither)),
+    )
+}
+
+struct DitherArgs<'d> {
+    ditherer: &'d mut Ditherer,
+    channel_count: usize,
+    scale: f64,
+}
+
+fn convert_from_dithered<S: Encoding>(
+    src: &[u8],
+    dst: &mut [u8],
+    dst_format: Format,
+    dither: &mut DitherArgs,
+) -> usize {
+    with_encoding!(dst_format, D => convert_pair_dithered::<S, D>(src, dst, dither))
+}
+
+/// The inner loop with dither, monomorphised for each pair of formats.
+fn convert_pair_dithered<S: Encoding, D: Encoding>(
+    src: &[u8],
+    dst: &mut [u8],
+    dither: &mut DitherArgs,
+) -> usize {
+    let count = min(src.len() / S::SIZE, dst.len() / D::SIZE);
+    let chunks = src.chunks_exact(S::SIZE).zip(dst.chunks_exact_mut(D::SIZE));
+    for (i, (s, d)) in chunks.enumerate() {
+        let x = S::decode(s).convert::<f64>();
+        let x = dither
+            .ditherer
+            .quantize(i % dither.channel_count, x, dither.scale);
+        D::encode(<D::Value as Sample>::from_f64(x), d);
+    }
+    count
+}
+
+/// A raw sample encoding. There is one per `Format`.
+pub(crate) trait Encoding {
+    /// The type that the sample is decoded to.
+    type Value: SampleValue;
+    /// The number of bytes per sample.
+    const SIZE: usize;
+
+    /// Decode a sample. `bytes` must be `SIZE` long.
+    fn decode(bytes: &[u8]) -> Self::Value;
+    /// Encode a sample. `bytes` must be `SIZE` long.
+    fn encode(v: Self::Value, bytes: &mut [u8]);
+}
+
+/// Conversion from a concrete sample type to any other, using the `Sample` trait.
+pub(crate) trait SampleValue: Sample + Copy {
+    fn convert<T: Sample>(self) -> T;
+}
+
+macro_rules! impl_sample_value {
+    ($($ty:ident => $from:ident),*) => {
+        $(
+            impl SampleValue for $ty {
+                #[inline]
+                fn convert<T: Sample>(self) -> T {
+                    T::$from(self)
+                }
+            }
+        )*
+    };
+}
+
+impl_sample_value!(
+    i8 => from_i8, u8 => from_u8, i16 => from_i16, u16 => from_u16, i24 => from_i24,
+    u24 => from_u24, i32 => from_i32, u32 => from_u32, f32 => from_f32, f64 => from_f64
+);
+
+/// Marker types that implement `Encoding` for each format.
+pub(crate) mod encoding {
+    use super::super::sample::*;
+    use super::Encoding;
+
+    macro_rules! encoding {
+        ($name:ident, $ty:ident, $size:expr, $from_bytes:ident, $to_bytes:ident) => {
+            pub struct $name;
+
+            impl Encoding for $name {
+                type Value = $ty;
+                const SIZE: usize = $size;
+
+                #[inline]
+                fn decode(bytes: &[u8]) -> $ty {
+                    let mut b = [0u8; $size];
+                    b.copy_from_slice(bytes);
+                    $ty::$from_bytes(b)
+                }
+                #[inline]
+                fn encode(v: $ty, bytes: &mut [u8]) {
+                    bytes.copy_from_slice(&v.$to_bytes());
+                }
+            }
+        };
+    }
+
+    // The 24-bit formats use the low three bytes of a 32-bit word.
+    macro_rules! encoding_24 {
+        ($name:ident, $ty:ident, $word:ident, $from_bytes:ident, $to_bytes:ident) => {
+            pub struct $name;
+
+            impl Encoding for $name {
+                type Value = $ty;
+                const SIZE: usize = 4;
+
+                #[inline]
+                fn decode(bytes: &[u8]) -> $ty {
+                    let mut b = [0u8; 4];
+                    b.copy_from_slice(bytes);
+                    $ty::from_word($word::$from_bytes(b))
+                }
+                #[inline]
+                fn encode(v: $ty, bytes: &mut [u8]) {
+                    bytes.copy_from_slice(&v.to_word().$to_bytes());
+                }
+            }
+        };
+    }
+
+    encoding!(S8, i8, 1, from_le_bytes, to_le_bytes);
+    encoding!(U8, u8, 1, from_le_bytes, to_le_bytes);
+    encoding!(S16LE, i16, 2, from_le_bytes, to_le_bytes);
+    encoding!(S16BE, i16, 2, from_be_bytes, to_be_bytes);
+    encoding!(U16LE, u16, 2, from_le_bytes, to_le_bytes);
+    encoding!(U16BE, u16, 2, from_be_bytes, to_be_bytes);
+    encoding_24!(S24LE, i24, i32, from_le_bytes, to_le_bytes);
+    encoding_24!(S24BE, i24, i32, from_be_bytes, to_be_bytes);
+    encoding_24!(U24LE, u24, u32, from_le_bytes, to_le_bytes);
+    encoding_24!(U24BE, u24, u32, from_be_bytes, to_be_bytes);
+    encoding!(S32LE, i32, 4, from_le_bytes, to_le_bytes);
+    encoding!(S32BE, i32, 4, from_be_bytes, to_be_bytes);
+    encoding!(U32LE, u32, 4, from_le_bytes, to_le_bytes);
+    encoding!(U32BE, u32, 4, from_be_bytes, to_be_bytes);
+    encoding!(Float32LE, f32, 4, from_le_bytes, to_le_bytes);
+    encoding!(Float32BE, f32, 4, from_be_bytes, to_be_bytes);
+    encoding!(Float64LE, f64, 8, from_le_bytes, to_le_bytes);
+    encoding!(Float64BE, f64, 8, from_be_bytes, to_be_bytes);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const ALL_FORMATS: [Format; 18] = [
+        Format::S8,
+        Format::U8,
+        Format::S16LE,
+        Format::S16BE,
+        Format::U16LE,
+        Format::U16BE,
+        Format::S24LE,
+        Format::S24BE,
+        Format::U24LE,
+        Format::U24BE,
+        Format::S32LE,
+        Format::S32BE,
+        Format::U32LE,
+        Format::U32BE,
+        Format::Float32LE,
+        Format::Float32BE,
+        Format::Float64LE,
+        Format::Float64BE,
+    ];
+
+    #[test]
+    fn round_trip_through_every_format() {
+        // These are exactly representable in every format.
+        let input = [0i8, 1, -1, 64, -128, 127];
+        let src: Vec<u8> = input.iter().map(|&x| x as u8).collect();
+
+        for &format in ALL_FORMATS.iter() {
+            let mut tmp = vec![0u8; input.len() * format.bytes_per_sample()];
+            let mut out = vec![0u8; input.len()];
+            assert_eq!(
+                convert_samples(&src, Format::S8, &mut tmp, format).unwrap(),
+                input.len()
+            );
+            assert_eq!(
+                convert_samples(&tmp, format, &mut out, Format::S8).unwrap(),
+                input.len()
+            );
+            assert_eq!(src, out, "Round trip through {:?}", format);
+        }
+    }
+
+    #[test]
+    fn twenty_four_bit_layout() {
+        let src = [0x12u8, 0x34, 0x56, 0x78];
+        let mut dst = [0u8; 4];
+
+        convert_samples(&src, Format::S32BE, &mut dst, Format::S24LE).unwrap();
+        assert_eq!(dst, [0x56, 0x34, 0x12, 0x00]);
+        convert_samples(&src, Format::S32BE, &mut dst, Format::S24BE).unwrap();
+        assert_eq!(dst, [0x00, 0x12, 0x34, 0x56]);
+
+        // Negative values are sign extended, and the high byte is ignored when decoding.
+        let src = [0xFFu8, 0xFF, 0xFF, 0x00];
+        convert_samples(&src, Format::S24LE, &mut dst, Format::S32LE).unwrap();
+        assert_eq!(dst, [0x00, 0xFF, 0xFF, 0xFF]);
+    }
+
+    #[test]
+    fn mismatched_lengths() {
+        let src = [0u8; 10];
+        let mut dst = [0u8; 12];
+        assert_eq!(
+            convert_samples(&src, Format::S16LE, &mut dst, Format::Float32LE).unwrap(),
+            3
+        );
+        assert!(convert_samples(&src, Format::Invalid, &mut dst, Format::S8).is_err());
+    }
+
+    #[test]
+    fn dithered_output_is_deterministic() {
+        let src: Vec<u8> = (0..64)
+            .flat_map(|i| ((i as f32 * 0.1).sin() * 0.01).to_le_bytes().to_vec())
+            .collect();
+        let mut a = vec![0u8; 64 * 2];
+        let mut b = vec![0u8; 64 * 2];
+        for dst in [&mut a, &mut b].iter_mut() {
+            let mut ditherer = Ditherer::with_seed(DitherKind::NoiseShaped, 99);
+            let n = convert_samples_dithered(
+                &src,
+                Format::Float32LE,
+                dst,
+                Format::S16BE,
+                2,
+                &mut ditherer,
+            )
+            .unwrap();
+            assert_eq!(n, 64);
+        }
+        assert_eq!(a, b);
+
+        // No precision is lost so there is no dither.
+        let mut ditherer = Ditherer::new(DitherKind::Triangular);
+        let src = [1u8, 2, 3, 4];
+        let mut dst = [0u8; 8];
+        convert_samples_dithered(&src, Format::S8, &mut dst, Format::S16LE, 1, &mut ditherer)
+            .unwrap();
+        assert_eq!(dst, [0, 1, 0, 2, 0, 3, 0, 4]);
+    }
+}
@@ -1,5 +1,7 @@
 extern crate libsoundio_sys as raw;
 
+use super::config::*;
+use super::context::*;
 use super::error::*;
 use super::format::*;
 use super::instream::*;
@@ -11,6 +13,7 @@ use super::util::*;
 use std::marker::PhantomData;
 use std::os::raw::c_int;
 use std::slice;
+use std::sync::{Arc, Mutex};
 
 /// Device represents an input or output device.
 ///
@@ -23,6 +26,10 @@ pub struct Device<'a> {
     /// This is just here to say that Device cannot outlive the Context it was created from.
     /// 'a is the lifetime of that Context.
     pub phantom: PhantomData<&'a ()>,
+
+    /// Held while the device is unreffed, since the event thread may be changing the
+    /// device list. See `Context::start_event_thread()`.
+    pub(crate) device_lock: Arc<Mutex<()>>,
 }
 
 impl<'a> Device<'a> {
@@ -262,6 +269,96 @@ impl<'a> Device<'a> {
         unsafe { raw::soundio_device_nearest_sample_rate(self.device, sample_rate as c_int) as i32 }
     }
 
+    /// Choose a stream configuration that this device supports, given lists of preferred
+    /// formats, sample rates and channel layouts in order of preference (most preferred first).
+    ///
+    /// For each parameter the first preference that the device supports is used. If none of
+    /// them are supported, the format and layout fall back to the device's current settings
+    /// (or the first supported value), preferring a layout with the same channel count as
+    /// your first choice, and the sample rate falls back to `nearest_sample_rate()` of your
+    /// first choice. An empty list means you have no preference and the device's current
+    /// setting is used.
+    ///
+    /// Any parameter where you didn't get your first choice is listed in
+    /// `NegotiatedConfig::compromises`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # fn foo() -> Result<(), soundio::Error> {
+    /// let mut ctx = soundio::Context::new();
+    /// ctx.connect_backend(soundio::Backend::Dummy)?;
+    /// let dev = ctx.default_output_device()?;
+    ///
+    /// let negotiated = dev.negotiate_config(
+    ///     &[soundio::native::Float32NE, soundio::native::S16NE],
+    ///     &[48000, 44100],
+    ///     &[soundio::ChannelLayout::get_builtin(soundio::ChannelLayoutId::Stereo)],
+    /// );
+    /// for c in &negotiated.compromises {
+    ///     println!("Compromise: {:?}", c);
+    /// }
+    ///
+    /// let stream = dev
+    ///     .outstream_builder()
+    ///     .config(negotiated.config)
+    ///     .write_callback(|stream: &mut soundio::OutStreamWriter| {})
+    ///     .open()?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn negotiate_config(
+        &self,
+        formats: &[Format],
+        sample_rates: &[i32],
+        layouts: &[ChannelLayout],
+    ) -> NegotiatedConfig {
+        let dev_formats = self.formats();
+        let dev_sample_rates = self.sample_rates();
+        let dev_layouts = self.layouts();
+        let current_layout = self.current_layout();
+
+        let caps = Capabilities {
+            formats: &dev_formats,
+            sample_rates: &dev_sample_rates,
+            layouts: &dev_layouts,
+            current_format: self.current_format(),
+            current_sample_rate: self.current_sample_rate(),
+            current_layout: &current_layout,
+        };
+
+        negotiate(&caps, formats, sample_rates, layouts, |r| {
+            self.nearest_sample_rate(r)
+        })
+    }
+
+    /// Create an `OutStreamBuilder` that can be used to configure and open an output
+    /// stream on this device. Any parameters that aren't set default to the device's
+    /// current settings. See `OutStreamBuilder` for more information.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # fn foo() -> Result<(), soundio::Error> {
+    /// let mut ctx = soundio::Context::new();
+    /// ctx.connect_backend(soundio::Backend::Dummy)?;
+    /// let dev = ctx.default_output_device()?;
+    /// let stream = dev
+    ///     .outstream_builder()
+    ///     .write_callback(|stream: &mut soundio::OutStreamWriter| {})
+    ///     .open()?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    ///
+    /// # Lifetimes
+    ///
+    /// `'a` is the lifetime of the `Device`. The `OutStream` lifetime `'b` must be less than or equal to `'a` (indicated by `'b: 'a`).
+    /// Also the callbacks must have a lifetime greater than or equal to `'b`. They do not need to be `'static`.
+    pub fn outstream_builder<'b: 'a>(&'a self) -> OutStreamBuilder<'a, 'b> {
+        OutStreamBuilder::new(self)
+    }
+
     /// After you call this function, SoundIoOutStream::software_latency is set to
     /// the correct value.
     ///
@@ -274,6 +371,9 @@ impl<'a> Device<'a> {
     /// using the functions on the `OutStream` that is returned. Then your write callback
     /// will be called. See the documentation on `OutStreamWriter` for more information.
     ///
+    /// This is a shortcut for `Device::outstream_builder()`, which also allows you to set
+    /// the stream name and libsoundio's `non_terminal_hint`.
+    ///
     /// The parameters are as follows.
     ///
     /// * `sample_rate` - The requested sample rate. Check supported sample rates first with `Device::sample_rates()`.
@@ -284,24 +384,10 @@ impl<'a> Device<'a> {
     /// * `underflow_callback` - Optional callback that is called when your `write_callback` is too slow and the output skips.
     /// * `error_callback` - Optional error callback.
     ///
-    /// Currently it is not possible to set the outstream name, or libsoundio's `non_terminal_hint`.
-    ///
     /// # Return Values
     ///
     /// If successful the function returns an `OutStream` which you can call `OutStream::start()` on,
-    /// otherwise it returns one of the following errors:
-    ///
-    /// * `Error::Invalid`
-    ///   - `aim()` is not `DeviceAim::Output`
-    ///   - `format` is not valid
-    ///   - `channel_count` is greater than `SOUNDIO_MAX_CHANNELS` (24).
-    /// * `Error::NoMem`
-    /// * `Error::OpeningDevice`
-    /// * `Error::BackendDisconnected`
-    /// * `Error::SystemResources`
-    /// * `Error::NoSuchClient` - when JACK returns `JackNoSuchClient`
-    /// * `Error::IncompatibleBackend` - `OutStream::channel_count()` is greater than the number of channels the backend can handle.
-    /// * `Error::IncompatibleDevice` - stream parameters requested are not compatible with the chosen device.
+    /// otherwise it returns one of the errors listed in `OutStreamBuilder::open()`.
     ///
     /// # Lifetimes
     ///
@@ -322,62 +408,58 @@ impl<'a> Device<'a> {
         UnderflowCB: 'b + FnMut(),
         ErrorCB: 'b + FnMut(Error),
     {
-        let mut outstream = unsafe { raw::soundio_outstream_create(self.device) };
-        if outstream.is_null() {
-            // Note that we should really abort() here (that's what the rest of Rust
-            // does on OOM), but there is no stable way to abort in Rust that I can see.
-            panic!("soundio_outstream_create() failed (out of memory).");
+        let mut builder = self
+            .outstream_builder()
+            .sample_rate(sample_rate)
+            .format(format)
+            .layout(layout)
+            .software_latency(latency)
+            .write_callback(write_callback);
+
+        if let Some(cb) = underflow_callback {
+            builder = builder.underflow_callback(cb);
         }
-
-        unsafe {
-            (*outstream).sample_rate = sample_rate;
-            (*outstream).format = format.into();
-            (*outstream).layout = layout.into();
-            (*outstream).software_latency = latency;
-            (*outstream).write_callback = outstream_write_callback;
-            (*outstream).underflow_callback = Some(outstream_underflow_callback);
-            (*outstream).error_callback = Some(outstream_error_callback);
+        if let Some(cb) = error_callback {
+            builder = builder.error_callback(cb);
         }
 
-        let mut stream = OutStream {
-            userdata: Box::new(OutStreamUserData {
-                outstream,
-                write_callback: Box::new(write_callback),
-                underflow_callback: match underflow_callback {
-                    Some(cb) => Some(Box::new(cb)),
-                    None => None,
-                },
-                error_callback: match error_callback {
-                    Some(cb) => Some(Box::new(cb)),
-                    None => None,
-                },
-            }),
-            phantom: PhantomData,
-        };
-
-        // Safe userdata pointer.
-        unsafe {
-            (*stream.userdata.outstream).userdata =
-                stream.userdata.as_mut() as *mut OutStreamUserData as *mut _;
-        }
-
-        match unsafe { raw::soundio_outstream_open(stream.userdata.outstream) } {
-            0 => {}
-            x => return Err(x.into()),
-        };
-
-        match unsafe { (*stream.userdata.outstream).layout_error } {
-            0 => {}
-            x => return Err(x.into()),
-        }
+        builder.open()
+    }
 
-        Ok(stream)
+    /// Create an `InStreamBuilder` that can be used to configure and open an input
+    /// stream on this device. Any parameters that aren't set default to the device's
+    /// current settings. See `InStreamBuilder` for more information.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # fn foo() -> Result<(), soundio::Error> {
+    /// let mut ctx = soundio::Context::new();
+    /// ctx.connect_backend(soundio::Backend::Dummy)?;
+    /// let dev = ctx.default_input_device()?;
+    /// let stream = dev
+    ///     .instream_builder()
+    ///     .read_callback(|stream: &mut soundio::InStreamReader| {})
+    ///     .open()?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    ///
+    /// # Lifetimes
+    ///
+    /// `'a` is the lifetime of the `Device`. The `InStream` lifetime `'b` must be less than or equal to `'a` (indicated by `'b: 'a`).
+    /// Also the callbacks must have a lifetime greater than or equal to `'b`. They do not need to be `'static`.
+    pub fn instream_builder<'b: 'a>(&'a self) -> InStreamBuilder<'a, 'b> {
+        InStreamBuilder::new(self)
     }
 
     /// Open an input stream on an input device. After opening you can start, pause and stop it
     /// using the functions on the `InStream` that is returned. Then your read callback
     /// will be called. See the documentation on `InStreamReader` for more information.
     ///
+    /// This is a shortcut for `Device::instream_builder()`, which also allows you to set
+    /// the stream name and libsoundio's `non_terminal_hint`.
+    ///
     /// The parameters are as follows.
     ///
     /// * `sample_rate` - The requested sample rate. Check supported sample rates first with `Device::sample_rates()`.
@@ -388,24 +470,10 @@ impl<'a> Device<'a> {
     /// * `overflow_callback` - Optional callback that is called when your `read_callback` is too slow and skips some input.
     /// * `error_callback` - Optional error callback.
     ///
-    /// Currently it is not possible to set the outstream name, or libsoundio's `non_terminal_hint`.
-    ///
     /// # Return Values
     ///
     /// If successful the function returns an `InStream` which you can call `InStream::start()` on,
-    /// otherwise it returns one of the following errors:
-    ///
-    /// * `Error::Invalid`
-    ///   - `aim()` is not `DeviceAim::Input`
-    ///   - `format` is not valid
-    ///   - `channel_count` is greater than `SOUNDIO_MAX_CHANNELS` (24).
-    /// * `Error::NoMem`
-    /// * `Error::OpeningDevice`
-    /// * `Error::BackendDisconnected`
-    /// * `Error::SystemResources`
-    /// * `Error::NoSuchClient` - when JACK returns `JackNoSuchClient`
-    /// * `Error::IncompatibleBackend` - `OutStream::channel_count()` is greater than the number of channels the backend can handle.
-    /// * `Error::IncompatibleDevice` - stream parameters requested are not compatible with the chosen device.
+    /// otherwise it returns one of the errors listed in `InStreamBuilder::open()`.
     ///
     /// # Lifetimes
     ///
@@ -426,61 +494,28 @@ impl<'a> Device<'a> {
         OverflowCB: 'b + FnMut(),
         ErrorCB: 'b + FnMut(Error),
     {
-        let mut instream = unsafe { raw::soundio_instream_create(self.device) };
-        if instream.is_null() {
-            // Note that we should really abort() here (that's what the rest of Rust
-            // does on OOM), but there is no stable way to abort in Rust that I can see.
-            panic!("soundio_instream_create() failed (out of memory).");
-        }
-
-        unsafe {
-            (*instream).sample_rate = sample_rate;
-            (*instream).format = format.into();
-            (*instream).layout = layout.into();
-            (*instream).software_latency = latency;
-            (*instream).read_callback = instream_read_callback;
-            (*instream).overflow_callback = Some(instream_overflow_callback);
-            (*instream).error_callback = Some(instream_error_callback);
+        let mut builder = self
+            .instream_builder()
+            .sample_rate(sample_rate)
+            .format(format)
+            .layout(layout)
+            .software_latency(latency)
+            .read_callback(read_callback);
+
+        if let Some(cb) = overflow_callback {
+            builder = builder.overflow_callback(cb);
         }
-
-        let mut stream = InStream {
-            userdata: Box::new(InStreamUserData {
-                instream,
-                read_callback: Box::new(read_callback),
-                overflow_callback: match overflow_callback {
-                    Some(cb) => Some(Box::new(cb)),
-                    None => None,
-                },
-                error_callback: match error_callback {
-                    Some(cb) => Some(Box::new(cb)),
-                    None => None,
-                },
-            }),
-            phantom: PhantomData,
-        };
-
-        // Safe userdata pointer.
-        unsafe {
-            (*stream.userdata.instream).userdata =
-                stream.userdata.as_mut() as *mut InStreamUserData as *mut _;
-        }
-
-        match unsafe { raw::soundio_instream_open(stream.userdata.instream) } {
-            0 => {}
-            x => return Err(x.into()),
-        };
-
-        match unsafe { (*stream.userdata.instream).layout_error } {
-            0 => {}
-            x => return Err(x.into()),
+        if let Some(cb) = error_callback {
+            builder = builder.error_callback(cb);
         }
 
-        Ok(stream)
+        builder.open()
     }
 }
 
 impl<'a> Drop for Device<'a> {
     fn drop(&mut self) {
+        let _lock = lock_devices(&self.device_lock);
         unsafe {
             raw::soundio_device_unref(self.device);
         }
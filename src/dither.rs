@@ -0,0 +1,159 @@
+extern crate libsoundio_sys as raw;
+
+use super::format::*;
+
+/// The type of dither used by a `Ditherer`.
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub enum DitherKind {
+    /// Uniform noise of one LSB peak to peak. This removes most quantisation
+    /// distortion but leaves noise modulation.
+    Rectangular,
+    /// Triangular probability density noise of two LSBs peak to peak. This is the usual
+    /// choice; the quantisation error becomes independent of the signal.
+    Triangular,
+    /// Triangular dither with first order error feedback, which moves the noise
+    /// towards higher frequencies where it is less audible.
+    NoiseShaped,
+}
+
+/// A `Ditherer` adds dither noise when reducing floating point samples to an integer
+/// format, to avoid the distortion caused by simple rounding on quiet material.
+///
+/// It can be used with `OutStreamWriter::set_sample_dithered()`, `convert_samples_dithered()`,
+/// or directly with `Ditherer::dither()`. It uses a simple pseudo-random number generator,
+/// so the output is deterministic for a given seed.
+///
+/// For noise shaping it keeps some state per channel, so you should use one `Ditherer`
+/// per stream and always pass the same channel index for a given channel.
+///
+/// # Examples
+///
+/// ```
+/// let mut ditherer = soundio::Ditherer::with_seed(soundio::DitherKind::Triangular, 1234);
+/// let x = ditherer.dither(0, 0.1234567, soundio::Format::S16LE);
+/// // x is now exactly representable as a 16-bit sample.
+/// assert_eq!(x * 32768.0, (x * 32768.0).round());
+/// ```
+#[derive(Clone)]
+pub struct Ditherer {
+    kind: DitherKind,
+    state: u32,
+    // The quantisation error of the previous sample on each channel, in LSBs.
+    error: [f64; raw::SOUNDIO_MAX_CHANNELS],
+}
+
+impl Ditherer {
+    /// Create a ditherer using a fixed default seed.
+    pub fn new(kind: DitherKind) -> Ditherer {
+        Ditherer::with_seed(kind, 0x9E37_79B9)
+    }
+
+    /// Create a ditherer with the given seed. The same seed always produces the same noise.
+    pub fn with_seed(kind: DitherKind, seed: u32) -> Ditherer {
+        Ditherer {
+            kind,
+            // Xorshift gets stuck at zero.
+            state: if seed == 0 { 1 } else { seed },
+            error: [0.0; raw::SOUNDIO_MAX_CHANNELS],
+        }
+    }
+
+    /// The type of dither.
+    pub fn kind(&self) -> DitherKind {
+        self.kind
+    }
+
+    /// Dither a sample (in the range -1.0 to 1.0) for the given format and return it
+    /// rounded to the nearest value that the format can represent exactly, so that it can
+    /// then be converted without any further rounding. Float formats are returned unchanged.
+    ///
+    /// Panics if `channel` is not less than `SOUNDIO_MAX_CHANNELS` (24).
+    pub fn dither(&mut self, channel: usize, sample: f64, format: Format) -> f64 {
+        match dither_scale(format) {
+            Some(scale) => self.quantize(channel, sample, scale),
+            None => sample,
+        }
+    }
+
+    /// Quantise `sample` to a grid of `1 / scale` with dither.
+    pub(crate) fn quantize(&mut self, channel: usize, sample: f64, scale: f64) -> f64 {
+        let clamp = |q: f64| q.clamp(-scale, scale - 1.0);
+        let x = sample * scale;
+        let q = match self.kind {
+            DitherKind::Rectangular => clamp((x + self.uniform()).round()),
+            DitherKind::Triangular => clamp((x + self.uniform() + self.uniform()).round()),
+            DitherKind::NoiseShaped => {
+                let target = x - self.error[channel];
+                let q = clamp((target + self.uniform() + self.uniform()).round());
+                // Normally the error is at most 1.5 LSBs. Limit it in case of clipping
+                // so that the filter recovers quickly.
+                self.error[channel] = (q - target).clamp(-2.0, 2.0);
+                q
+            }
+        };
+        q / scale
+    }
+
+    // Uniform noise from -0.5 to 0.5, using xorshift32.
+    fn uniform(&mut self) -> f64 {
+        self.state ^= self.state << 13;
+        self.state ^= self.state >> 17;
+        self.state ^= self.state << 5;
+        f64::from(self.state) / 4_294_967_296.0 - 0.5
+    }
+}
+
+/// The number of steps per unit for an integer format, or `None` if it is a float
+/// format and doesn't need dithering.
+pub(crate) fn dither_scale(format: Format) -> Option<f64> {
+    match format {
+        Format::S8 | Format::U8 => Some(128.0),
+        Format::S16LE | Format::S16BE | Format::U16LE | Format::U16BE => Some(32_768.0),
+        Format::S24LE | Format::S24BE | Format::U24LE | Format::U24BE => Some(8_388_608.0),
+        Format::S32LE | Format::S32BE | Format::U32LE | Format::U32BE => Some(2_147_483_648.0),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn deterministic() {
+        for &kind in [
+            DitherKind::Rectangular,
+            DitherKind::Triangular,
+            DitherKind::NoiseShaped,
+        ]
+        .iter()
+        {
+            let mut a = Ditherer::with_seed(kind, 42);
+            let mut b = Ditherer::with_seed(kind, 42);
+            for i in 0..1000 {
+                let x = (i as f64 * 0.01).sin() * 0.001;
+                let y = a.dither(i % 2, x, Format::S16LE);
+                assert_eq!(y, b.dither(i % 2, x, Format::S16LE));
+                assert_eq!(y * 32768.0, (y * 32768.0).round());
+            }
+        }
+    }
+
+    #[test]
+    fn triangular_is_unbiased() {
+        // A constant between two 8-bit levels should average out to itself.
+        let mut d = Ditherer::with_seed(DitherKind::Triangular, 7);
+        let x = 0.3 / 128.0;
+        let n = 100_000;
+        let sum: f64 = (0..n).map(|_| d.dither(0, x, Format::S8)).sum();
+        assert!((sum / n as f64 - x).abs() < 0.02 / 128.0);
+    }
+
+    #[test]
+    fn clamped_and_float_passthrough() {
+        let mut d = Ditherer::new(DitherKind::Rectangular);
+        assert_eq!(d.dither(0, 1.0, Format::S8), 127.0 / 128.0);
+        assert_eq!(d.dither(0, -1.0, Format::S8), -1.0);
+        assert_eq!(d.dither(0, 0.123, Format::Float32LE), 0.123);
+    }
+}
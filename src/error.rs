@@ -11,6 +11,11 @@ use std::os::raw::c_int;
 /// taken directly from libsoundio. It supports conversion to `String` using
 /// the `From` trait.
 ///
+/// `UnsupportedFormat`, `UnsupportedLayout` and `UnsupportedSampleRate` are not
+/// libsoundio errors. They are converted to libsoundio's `IncompatibleDevice` code
+/// by `From<Error> for c_int`, so converting them to a `c_int` and back gives
+/// `Error::IncompatibleDevice`.
+///
 /// # Examples
 ///
 /// ```
@@ -52,6 +57,15 @@ pub enum Error {
     EncodingString,
     /// Unknown error that libsoundio should never return.
     Unknown,
+    /// The requested format is not supported by the device. Returned by
+    /// soundio-rs before the stream is opened.
+    UnsupportedFormat,
+    /// The requested channel layout is not supported by the device. Returned by
+    /// soundio-rs before the stream is opened.
+    UnsupportedLayout,
+    /// The requested sample rate is not supported by the device. Returned by
+    /// soundio-rs before the stream is opened.
+    UnsupportedSampleRate,
 }
 
 impl From<c_int> for Error {
@@ -96,6 +110,10 @@ impl From<Error> for c_int {
             Error::Underflow => 14,
             Error::EncodingString => 15,
             Error::Unknown => -1, // This should never happen really.
+            // These are ours rather than libsoundio's, so map them to the closest match.
+            Error::UnsupportedFormat => 9,
+            Error::UnsupportedLayout => 9,
+            Error::UnsupportedSampleRate => 9,
         }
     }
 }
@@ -114,6 +132,13 @@ impl fmt::Display for Error {
 // Implement the description for errors using soundio_strerror(), and the cause which we never know.
 impl error::Error for Error {
     fn description(&self) -> &str {
+        match *self {
+            Error::UnsupportedFormat => return "Format not supported by device",
+            Error::UnsupportedLayout => return "Channel layout not supported by device",
+            Error::UnsupportedSampleRate => return "Sample rate not supported by device",
+            _ => {}
+        }
+
         let c_str: &CStr = unsafe { CStr::from_ptr(raw::soundio_strerror((*self).into())) };
 
         // to_str() checks for valid UTF-8 since that what a &str is. For now at least there are
@@ -0,0 +1,140 @@
+use super::error::*;
+use super::events::*;
+
+use futures_core::Stream;
+
+use std::pin::Pin;
+use std::sync::Arc;
+use std::task::{self, Poll};
+
+/// A `futures_core::Stream` of the events queued in an `EventSink`, so that an async
+/// task can await device changes, backend disconnects, stream errors and underflows
+/// instead of polling. It is only available with the `futures` feature, and doesn't
+/// depend on any particular runtime.
+///
+/// The task is woken whenever an event is queued. Stream events are recorded on the
+/// audio thread, so the waker is called there and should be cheap. Device changes and
+/// backend disconnects are only recorded when the context flushes its events, so use
+/// `Context::start_event_thread()` to flush them as soon as libsoundio signals them.
+///
+/// The stream never ends. Only one task should poll the streams of a sink at a time,
+/// since only the most recent task is woken.
+///
+/// # Examples
+///
+/// ```
+/// # extern crate futures_core;
+/// # extern crate soundio;
+/// use futures_core::Stream;
+/// use std::sync::Arc;
+///
+/// # fn foo() -> Result<(), soundio::Error> {
+/// let sink = Arc::new(soundio::EventSink::with_queue(64));
+///
+/// let mut ctx = soundio::Context::new();
+/// ctx.set_event_sink(sink.clone());
+/// ctx.connect()?;
+/// ctx.start_event_thread(None::<fn()>, Some(|_| {}))?;
+///
+/// let dev = ctx.default_output_device()?;
+/// let mut stream = dev
+///     .outstream_builder()
+///     .event_sink(sink.clone())
+///     .write_callback(|stream: &mut soundio::OutStreamWriter| {
+///         let frame_count_max = stream.frame_count_max();
+///         stream.begin_write(frame_count_max).unwrap();
+///     })
+///     .open()?;
+/// stream.start()?;
+///
+/// // Poll this from a task, e.g. with `StreamExt::next()` from the `futures` crate.
+/// let events = soundio::EventStream::new(sink)?;
+/// # fn check<S: Stream<Item = soundio::Event>>(_: S) {}
+/// # check(events);
+/// # Ok(())
+/// # }
+/// ```
+pub struct EventStream {
+    sink: Arc<EventSink>,
+}
+
+impl EventStream {
+    /// Create a stream of the events queued in `sink`.
+    ///
+    /// # Errors
+    ///
+    /// * `Error::Invalid` - `sink` has no queue. See `EventSink::with_queue()`.
+    pub fn new(sink: Arc<EventSink>) -> Result<EventStream> {
+        if !sink.has_queue() {
+            return Err(Error::Invalid);
+        }
+        Ok(EventStream { sink })
+    }
+
+    /// The sink that the events come from.
+    pub fn sink(&self) -> &Arc<EventSink> {
+        &self.sink
+    }
+}
+
+impl Stream for EventStream {
+    type Item = Event;
+
+    fn poll_next(self: Pin<&mut Self>, cx: &mut task::Context<'_>) -> Poll<Option<Event>> {
+        if let Some(event) = self.sink.pop() {
+            return Poll::Ready(Some(event));
+        }
+        // Check again after registering, in case an event was queued in between.
+        self.sink.register_waker(cx.waker());
+        match self.sink.pop() {
+            Some(event) => Poll::Ready(Some(event)),
+            None => Poll::Pending,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::task::{Wake, Waker};
+    use std::thread;
+
+    struct CountWakes(AtomicUsize);
+
+    impl Wake for CountWakes {
+        fn wake(self: Arc<Self>) {
+            self.0.fetch_add(1, Ordering::SeqCst);
+        }
+    }
+
+    #[test]
+    fn wakes_on_event() {
+        let sink = Arc::new(EventSink::with_queue(8));
+        let mut events = EventStream::new(sink.clone()).unwrap();
+        let wakes = Arc::new(CountWakes(AtomicUsize::new(0)));
+        let waker = Waker::from(wakes.clone());
+        let mut cx = task::Context::from_waker(&waker);
+
+        match Pin::new(&mut events).poll_next(&mut cx) {
+            Poll::Pending => {}
+            p => panic!("Unexpected {:?}", p),
+        }
+        thread::spawn(move || sink.record(Event::Underflow))
+            .join()
+            .unwrap();
+        assert_eq!(wakes.0.load(Ordering::SeqCst), 1);
+        match Pin::new(&mut events).poll_next(&mut cx) {
+            Poll::Ready(Some(Event::Underflow)) => {}
+            p => panic!("Unexpected {:?}", p),
+        }
+    }
+
+    #[test]
+    fn needs_queue() {
+        match EventStream::new(Arc::new(EventSink::new())) {
+            Err(Error::Invalid) => {}
+            r => panic!("Unexpected {:?}", r.map(|_| ())),
+        }
+    }
+}
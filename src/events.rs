@@ -0,0 +1,309 @@
+use super::error::*;
+use super::queue::*;
+
+use std::fmt;
+use std::os::raw::c_int;
+use std::sync::atomic::{AtomicI32, AtomicUsize, Ordering};
+#[cfg(feature = "futures")]
+use std::sync::Mutex;
+#[cfg(feature = "futures")]
+use std::task::Waker;
+
+/// Something that happened on an audio thread or in a context callback. Events are
+/// recorded in an `EventSink`.
+#[derive(Debug, Copy, Clone)]
+pub enum Event {
+    /// The output stream underflowed. See `OutStreamBuilder::underflow_callback()`.
+    Underflow,
+    /// The input stream overflowed. See `InStreamBuilder::overflow_callback()`.
+    Overflow,
+    /// The stream had an error and must be destroyed. See `OutStreamBuilder::error_callback()`.
+    StreamError(Error),
+    /// Ending a write failed when an `OutStreamWriter` was dropped.
+    EndWriteError(Error),
+    /// Ending a read failed when an `InStreamReader` was dropped.
+    EndReadError(Error),
+    /// The list of devices changed.
+    DevicesChanged,
+    /// The backend disconnected. See `Context::new_with_callbacks()`.
+    BackendDisconnected(Error),
+}
+
+impl fmt::Display for Event {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            Event::Underflow => write!(f, "Underflow"),
+            Event::Overflow => write!(f, "Overflow"),
+            Event::StreamError(e) => write!(f, "Stream error: {}", e),
+            Event::EndWriteError(e) => write!(f, "Error writing outstream: {}", e),
+            Event::EndReadError(e) => write!(f, "Error reading instream: {}", e),
+            Event::DevicesChanged => write!(f, "Devices changed"),
+            Event::BackendDisconnected(e) => write!(f, "Backend disconnected: {}", e),
+        }
+    }
+}
+
+/// An `EventSink` records events from the stream and context callbacks without allocating
+/// or locking, so it is safe to use from real-time threads.
+///
+/// It always counts the events, and can optionally keep them in a fixed size lock-free
+/// queue so that a non-real-time thread can `drain()` them and forward them to a logger.
+/// If the queue is full new events are only counted; see `dropped_count()`.
+///
+/// Every stream and context has its own sink by default, which only counts events. You can
+/// share one between several streams with `OutStreamBuilder::event_sink()`,
+/// `InStreamBuilder::event_sink()` and `Context::set_event_sink()`.
+///
+/// # Examples
+///
+/// ```
+/// use std::sync::Arc;
+///
+/// let sink = Arc::new(soundio::EventSink::with_queue(64));
+/// sink.record(soundio::Event::Underflow);
+///
+/// // On a non-real-time thread.
+/// for event in sink.drain() {
+///     println!("{}", event);
+/// }
+/// assert_eq!(sink.underflow_count(), 1);
+/// ```
+pub struct EventSink {
+    underflows: AtomicUsize,
+    overflows: AtomicUsize,
+    stream_errors: AtomicUsize,
+    end_errors: AtomicUsize,
+    devices_changes: AtomicUsize,
+    backend_disconnects: AtomicUsize,
+    dropped: AtomicUsize,
+    // The error code of the last end write or read error, or 0.
+    last_end_error: AtomicI32,
+    queue: Option<BoundedQueue<Event>>,
+    // The task waiting in an `EventStream`, which is woken when an event is queued.
+    #[cfg(feature = "futures")]
+    waker: Mutex<Option<Waker>>,
+}
+
+impl EventSink {
+    /// Create a sink that only counts events.
+    pub fn new() -> EventSink {
+        EventSink::create(None)
+    }
+
+    /// Create a sink that counts events and also queues up to `capacity` of them until
+    /// they are drained. The capacity is rounded up to a power of two.
+    pub fn with_queue(capacity: usize) -> EventSink {
+        EventSink::create(Some(BoundedQueue::new(capacity)))
+    }
+
+    fn create(queue: Option<BoundedQueue<Event>>) -> EventSink {
+        EventSink {
+            underflows: AtomicUsize::new(0),
+            overflows: AtomicUsize::new(0),
+            stream_errors: AtomicUsize::new(0),
+            end_errors: AtomicUsize::new(0),
+            devices_changes: AtomicUsize::new(0),
+            backend_disconnects: AtomicUsize::new(0),
+            dropped: AtomicUsize::new(0),
+            last_end_error: AtomicI32::new(0),
+            queue,
+            #[cfg(feature = "futures")]
+            waker: Mutex::new(None),
+        }
+    }
+
+    /// Record an event. This never blocks or allocates.
+    pub fn record(&self, event: Event) {
+        let counter = match event {
+            Event::Underflow => &self.underflows,
+            Event::Overflow => &self.overflows,
+            Event::StreamError(_) => &self.stream_errors,
+            Event::EndWriteError(_) | Event::EndReadError(_) => &self.end_errors,
+            Event::DevicesChanged => &self.devices_changes,
+            Event::BackendDisconnected(_) => &self.backend_disconnects,
+        };
+        counter.fetch_add(1, Ordering::Relaxed);
+
+        if let Event::EndWriteError(e) | Event::EndReadError(e) = event {
+            self.last_end_error.store(c_int::from(e), Ordering::Relaxed);
+        }
+
+        if let Some(ref queue) = self.queue {
+            if queue.push(event).is_err() {
+                self.dropped.fetch_add(1, Ordering::Relaxed);
+            } else {
+                #[cfg(feature = "futures")]
+                self.wake();
+            }
+        }
+    }
+
+    // Wake the task waiting for events. This must not block, so if the lock is held it
+    // does nothing: either the task is registering its waker and checks the queue
+    // afterwards, or another thread is waking it.
+    #[cfg(feature = "futures")]
+    fn wake(&self) {
+        if let Ok(waker) = self.waker.try_lock() {
+            if let Some(ref waker) = *waker {
+                waker.wake_by_ref();
+            }
+        }
+    }
+
+    /// Set the task to wake when an event is queued.
+    #[cfg(feature = "futures")]
+    pub(crate) fn register_waker(&self, waker: &Waker) {
+        let mut current = self.waker.lock().unwrap_or_else(|e| e.into_inner());
+        match *current {
+            Some(ref w) if w.will_wake(waker) => {}
+            _ => *current = Some(waker.clone()),
+        }
+    }
+
+    /// Returns true if the sink queues events, i.e. it was created with `with_queue()`.
+    pub fn has_queue(&self) -> bool {
+        self.queue.is_some()
+    }
+
+    /// Remove the oldest queued event. This always returns `None` if the sink has no queue.
+    pub fn pop(&self) -> Option<Event> {
+        self.queue.as_ref().and_then(|q| q.pop())
+    }
+
+    /// Iterate over the queued events, removing them.
+    pub fn drain(&self) -> Drain<'_> {
+        Drain { sink: self }
+    }
+
+    /// The number of `Event::Underflow` events.
+    pub fn underflow_count(&self) -> usize {
+        self.underflows.load(Ordering::Relaxed)
+    }
+
+    /// The number of `Event::Overflow` events.
+    pub fn overflow_count(&self) -> usize {
+        self.overflows.load(Ordering::Relaxed)
+    }
+
+    /// The number of `Event::StreamError` events.
+    pub fn stream_error_count(&self) -> usize {
+        self.stream_errors.load(Ordering::Relaxed)
+    }
+
+    /// The number of `Event::EndWriteError` and `Event::EndReadError` events.
+    pub fn end_error_count(&self) -> usize {
+        self.end_errors.load(Ordering::Relaxed)
+    }
+
+    /// Return the error of the most recent `Event::EndWriteError` or `Event::EndReadError`
+    /// and reset it, so that the next call returns `None` until there is another error.
+    /// These are the errors from ending a write or read when an `OutStreamWriter` or
+    /// `InStreamReader` is dropped.
+    pub fn take_last_end_error(&self) -> Option<Error> {
+        match self.last_end_error.swap(0, Ordering::Relaxed) {
+            0 => None,
+            e => Some(e.into()),
+        }
+    }
+
+    /// The number of `Event::DevicesChanged` events.
+    pub fn devices_change_count(&self) -> usize {
+        self.devices_changes.load(Ordering::Relaxed)
+    }
+
+    /// The number of `Event::BackendDisconnected` events.
+    pub fn backend_disconnect_count(&self) -> usize {
+        self.backend_disconnects.load(Ordering::Relaxed)
+    }
+
+    /// The number of events that were counted but not queued because the queue was full.
+    pub fn dropped_count(&self) -> usize {
+        self.dropped.load(Ordering::Relaxed)
+    }
+}
+
+/// An iterator that removes the queued events from an `EventSink`. It is returned by
+/// `EventSink::drain()`.
+pub struct Drain<'a> {
+    sink: &'a EventSink,
+}
+
+impl<'a> Iterator for Drain<'a> {
+    type Item = Event;
+
+    fn next(&mut self) -> Option<Event> {
+        self.sink.pop()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Arc;
+    use std::thread;
+
+    #[test]
+    fn counts_without_queue() {
+        let sink = EventSink::new();
+        sink.record(Event::Underflow);
+        sink.record(Event::Underflow);
+        sink.record(Event::EndReadError(Error::Streaming));
+        assert_eq!(sink.underflow_count(), 2);
+        assert_eq!(sink.end_error_count(), 1);
+        assert_eq!(sink.overflow_count(), 0);
+        assert!(sink.pop().is_none());
+        assert_eq!(sink.dropped_count(), 0);
+    }
+
+    #[test]
+    fn last_end_error() {
+        let sink = EventSink::new();
+        assert!(sink.take_last_end_error().is_none());
+        sink.record(Event::EndWriteError(Error::Streaming));
+        sink.record(Event::EndWriteError(Error::Underflow));
+        sink.record(Event::Underflow);
+        match sink.take_last_end_error() {
+            Some(Error::Underflow) => {}
+            e => panic!("Unexpected {:?}", e),
+        }
+        assert!(sink.take_last_end_error().is_none());
+    }
+
+    #[test]
+    fn queue_full_and_wrap() {
+        let sink = EventSink::with_queue(3);
+        for _ in 0..10 {
+            for _ in 0..5 {
+                sink.record(Event::Overflow);
+            }
+            assert_eq!(sink.drain().count(), 4);
+        }
+        assert_eq!(sink.overflow_count(), 50);
+        assert_eq!(sink.dropped_count(), 10);
+    }
+
+    #[test]
+    fn many_producers() {
+        let sink = Arc::new(EventSink::with_queue(1024));
+        let threads: Vec<_> = (0..4)
+            .map(|_| {
+                let sink = sink.clone();
+                thread::spawn(move || {
+                    for _ in 0..200 {
+                        sink.record(Event::Underflow);
+                    }
+                })
+            })
+            .collect();
+
+        let mut received = 0;
+        while received < 800 {
+            received += sink.drain().count();
+        }
+        for t in threads {
+            t.join().unwrap();
+        }
+        assert_eq!(received, 800);
+        assert_eq!(sink.dropped_count(), 0);
+    }
+}
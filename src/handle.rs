@@ -0,0 +1,327 @@
+extern crate libsoundio_sys as raw;
+
+use super::error::*;
+use super::volume::*;
+
+use std::ptr;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::{Arc, Mutex, MutexGuard};
+
+/// The state shared between a stream and its handles.
+///
+/// The raw stream pointer is behind a mutex and is set to null just before the stream is
+/// destroyed, so a handle can never use a destroyed stream. The mutex is not held while
+/// the stream is destroyed because that waits for the callback thread, which might be
+/// using a handle itself.
+pub(crate) struct StreamControl<S> {
+    stream: Mutex<*mut S>,
+    stop_requested: AtomicBool,
+    // Set when there is a handle, so that the callback knows to measure the latency.
+    latency_wanted: AtomicBool,
+    // The latency measured in the last callback in seconds as `f64` bits, or `NO_LATENCY`.
+    latency: AtomicU64,
+    // The number of frames written or read, which is only changed in the callback.
+    frame_position: AtomicU64,
+}
+
+// The pointer is only used while the mutex is locked, and libsoundio allows the functions
+// that the handles call to be called from any thread.
+unsafe impl<S> Send for StreamControl<S> {}
+unsafe impl<S> Sync for StreamControl<S> {}
+
+// NaN, which a real latency can never be.
+const NO_LATENCY: u64 = 0x7FF8_0000_0000_0000;
+
+impl<S> StreamControl<S> {
+    pub(crate) fn new(stream: *mut S) -> StreamControl<S> {
+        StreamControl {
+            stream: Mutex::new(stream),
+            stop_requested: AtomicBool::new(false),
+            latency_wanted: AtomicBool::new(false),
+            latency: AtomicU64::new(NO_LATENCY),
+            frame_position: AtomicU64::new(0),
+        }
+    }
+
+    // A panic while the lock is held can't leave the pointer in a bad state.
+    fn lock(&self) -> MutexGuard<'_, *mut S> {
+        self.stream.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    /// Call `f` with the stream pointer, or return `Error::Invalid` if it has been destroyed.
+    fn with_stream<F>(&self, f: F) -> Result<()>
+    where
+        F: FnOnce(*mut S) -> i32,
+    {
+        let stream = self.lock();
+        if stream.is_null() {
+            return Err(Error::Invalid);
+        }
+        match f(*stream) {
+            0 => Ok(()),
+            e => Err(e.into()),
+        }
+    }
+
+    /// Mark the stream as destroyed. This must be called before destroying it.
+    pub(crate) fn close(&self) {
+        *self.lock() = ptr::null_mut();
+    }
+
+    fn is_closed(&self) -> bool {
+        self.lock().is_null()
+    }
+
+    pub(crate) fn request_stop(&self) {
+        self.stop_requested.store(true, Ordering::Release);
+    }
+
+    pub(crate) fn stop_requested(&self) -> bool {
+        self.stop_requested.load(Ordering::Acquire)
+    }
+
+    pub(crate) fn latency_wanted(&self) -> bool {
+        self.latency_wanted.load(Ordering::Relaxed)
+    }
+
+    pub(crate) fn set_latency(&self, latency: f64) {
+        self.latency.store(latency.to_bits(), Ordering::Relaxed);
+    }
+
+    fn latency(&self) -> Option<f64> {
+        match self.latency.load(Ordering::Relaxed) {
+            NO_LATENCY => None,
+            bits => Some(f64::from_bits(bits)),
+        }
+    }
+
+    pub(crate) fn frame_position(&self) -> u64 {
+        self.frame_position.load(Ordering::Relaxed)
+    }
+
+    pub(crate) fn advance(&self, frames: usize) {
+        self.frame_position
+            .fetch_add(frames as u64, Ordering::Relaxed);
+    }
+}
+
+/// A handle to control an `OutStream` from any thread. It is obtained with
+/// `OutStream::handle()` and can be cloned and sent to other threads.
+///
+/// The handle does not keep the stream alive. Once the `OutStream` has been dropped all
+/// of the functions that control the stream return `Error::Invalid`.
+///
+/// # Examples
+///
+/// ```
+/// # fn foo() -> Result<(), soundio::Error> {
+/// let mut ctx = soundio::Context::new();
+/// ctx.connect_backend(soundio::Backend::Dummy)?;
+/// ctx.flush_events();
+/// let dev = ctx.default_output_device()?;
+///
+/// let mut stream = dev
+///     .outstream_builder()
+///     .write_callback(|stream: &mut soundio::OutStreamWriter| {
+///         if stream.stop_requested() {
+///             // Fade out or write silence.
+///         }
+///     })
+///     .open()?;
+/// stream.start()?;
+///
+/// let handle = stream.handle();
+/// std::thread::spawn(move || {
+///     handle.pause(true).ok();
+///     handle.set_volume(0.5).ok();
+///     println!("Latency: {:?}", handle.latency());
+///     handle.request_stop();
+/// })
+/// .join()
+/// .unwrap();
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Clone)]
+pub struct OutStreamHandle {
+    control: Arc<StreamControl<raw::SoundIoOutStream>>,
+    volume: Arc<VolumeControl>,
+}
+
+impl OutStreamHandle {
+    pub(crate) fn new(
+        control: Arc<StreamControl<raw::SoundIoOutStream>>,
+        volume: Arc<VolumeControl>,
+    ) -> OutStreamHandle {
+        control.latency_wanted.store(true, Ordering::Relaxed);
+        OutStreamHandle { control, volume }
+    }
+
+    /// Pause or unpause the stream. See `OutStream::pause()`.
+    ///
+    /// # Errors
+    ///
+    /// * `Error::Invalid` - the stream has been dropped.
+    /// * The errors returned by `OutStream::pause()`.
+    pub fn pause(&self, pause: bool) -> Result<()> {
+        self.control
+            .with_stream(|s| unsafe { raw::soundio_outstream_pause(s, pause as i8) })
+    }
+
+    /// Clear the output stream buffer. See `OutStream::clear_buffer()`.
+    ///
+    /// # Errors
+    ///
+    /// * `Error::Invalid` - the stream has been dropped.
+    /// * The errors returned by `OutStream::clear_buffer()`.
+    pub fn clear_buffer(&self) -> Result<()> {
+        self.control
+            .with_stream(|s| unsafe { raw::soundio_outstream_clear_buffer(s) })
+    }
+
+    /// Set the volume of the stream. See `OutStream::set_volume()`.
+    ///
+    /// # Errors
+    ///
+    /// * `Error::Invalid` - the stream has been dropped, or `volume` is not between
+    ///   0.0 and 1.0.
+    /// * The errors returned by `OutStream::set_volume()`.
+    pub fn set_volume(&self, volume: f32) -> Result<()> {
+        if self.control.is_closed() {
+            return Err(Error::Invalid);
+        }
+        let control = &self.control;
+        self.volume.set(volume, |v| {
+            control.with_stream(|s| unsafe { raw::soundio_outstream_set_volume(s, v) })
+        })
+    }
+
+    /// The volume of the stream. See `OutStream::volume()`.
+    pub fn volume(&self) -> f32 {
+        self.volume.volume()
+    }
+
+    /// The latency in seconds measured at the end of the most recent write callback, as
+    /// returned by `OutStreamWriter::get_latency()`. libsoundio only allows measuring it
+    /// in the callback, so this is `None` until the callback has run after the handle
+    /// was created.
+    pub fn latency(&self) -> Option<f64> {
+        self.control.latency()
+    }
+
+    /// The number of frames that have been written to the stream. See
+    /// `OutStreamWriter::frame_position()`.
+    pub fn frame_position(&self) -> u64 {
+        self.control.frame_position()
+    }
+
+    /// Set the "stop requested" flag. It doesn't do anything by itself, but the write
+    /// callback can check it with `OutStreamWriter::stop_requested()`.
+    pub fn request_stop(&self) {
+        self.control.request_stop()
+    }
+
+    /// Returns true if `request_stop()` has been called on any handle or on the
+    /// `OutStreamWriter`.
+    pub fn stop_requested(&self) -> bool {
+        self.control.stop_requested()
+    }
+
+    /// Returns true if the stream has been dropped.
+    pub fn is_closed(&self) -> bool {
+        self.control.is_closed()
+    }
+}
+
+/// A handle to control an `InStream` from any thread. It is obtained with
+/// `InStream::handle()` and can be cloned and sent to other threads.
+///
+/// The handle does not keep the stream alive. Once the `InStream` has been dropped all
+/// of the functions that control the stream return `Error::Invalid`.
+#[derive(Clone)]
+pub struct InStreamHandle {
+    control: Arc<StreamControl<raw::SoundIoInStream>>,
+}
+
+impl InStreamHandle {
+    pub(crate) fn new(control: Arc<StreamControl<raw::SoundIoInStream>>) -> InStreamHandle {
+        control.latency_wanted.store(true, Ordering::Relaxed);
+        InStreamHandle { control }
+    }
+
+    /// Pause or unpause the stream. See `InStream::pause()`.
+    ///
+    /// # Errors
+    ///
+    /// * `Error::Invalid` - the stream has been dropped.
+    /// * The errors returned by `InStream::pause()`.
+    pub fn pause(&self, pause: bool) -> Result<()> {
+        self.control
+            .with_stream(|s| unsafe { raw::soundio_instream_pause(s, pause as i8) })
+    }
+
+    /// The latency in seconds measured at the end of the most recent read callback, as
+    /// returned by `InStreamReader::get_latency()`. libsoundio only allows measuring it
+    /// in the callback, so this is `None` until the callback has run after the handle
+    /// was created.
+    pub fn latency(&self) -> Option<f64> {
+        self.control.latency()
+    }
+
+    /// The number of frames that have been read from the stream. See
+    /// `InStreamReader::frame_position()`.
+    pub fn frame_position(&self) -> u64 {
+        self.control.frame_position()
+    }
+
+    /// Set the "stop requested" flag. It doesn't do anything by itself, but the read
+    /// callback can check it with `InStreamReader::stop_requested()`.
+    pub fn request_stop(&self) {
+        self.control.request_stop()
+    }
+
+    /// Returns true if `request_stop()` has been called on any handle or on the
+    /// `InStreamReader`.
+    pub fn stop_requested(&self) -> bool {
+        self.control.stop_requested()
+    }
+
+    /// Returns true if the stream has been dropped.
+    pub fn is_closed(&self) -> bool {
+        self.control.is_closed()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::thread;
+
+    #[test]
+    fn handles_are_send_sync() {
+        fn check<T: Send + Sync + Clone>() {}
+        check::<OutStreamHandle>();
+        check::<InStreamHandle>();
+    }
+
+    #[test]
+    fn closed_stream() {
+        let mut dummy = 0u8;
+        let control = Arc::new(StreamControl::new(&mut dummy as *mut u8));
+        assert!(control.with_stream(|_| 0).is_ok());
+        assert_eq!(control.latency(), None);
+        control.set_latency(0.25);
+        assert_eq!(control.latency(), Some(0.25));
+        control.advance(100);
+        control.advance(28);
+        assert_eq!(control.frame_position(), 128);
+
+        let other = control.clone();
+        thread::spawn(move || other.close()).join().unwrap();
+        assert!(control.is_closed());
+        match control.with_stream(|_| panic!("Used a closed stream")) {
+            Err(Error::Invalid) => {}
+            r => panic!("Unexpected {:?}", r),
+        }
+    }
+}
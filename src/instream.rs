@@ -1,14 +1,28 @@
 extern crate libsoundio_sys as raw;
 
+use super::blocking::*;
+use super::buffer::*;
+use super::config::*;
+use super::context::*;
+use super::device::*;
 use super::error::*;
+use super::events::*;
 use super::format::*;
+use super::handle::*;
+use super::layout::*;
+use super::ring_buffer::*;
 use super::sample::*;
+use super::timestamp::*;
+use super::typed::*;
 use super::util::*;
 
+use std::ffi::CString;
 use std::marker::PhantomData;
 use std::os::raw::{c_double, c_int};
 use std::ptr;
+use std::result;
 use std::slice;
+use std::sync::{Arc, Mutex};
 
 /// This is called when an instream has been read. The `InStreamUserData` struct is obtained
 /// from the stream.userdata, then the user-supplied callback is called with an `InStreamReader`
@@ -24,6 +38,8 @@ pub extern "C" fn instream_read_callback(
 
     let mut stream_reader = InStreamReader {
         instream: userdata.instream,
+        event_sink: &userdata.event_sink,
+        control: &userdata.control,
         frame_count_min: frame_count_min as _,
         frame_count_max: frame_count_max as _,
         read_started: false,
@@ -33,6 +49,15 @@ pub extern "C" fn instream_read_callback(
     };
 
     (userdata.read_callback)(&mut stream_reader);
+    drop(stream_reader);
+
+    // Handles can't measure the latency themselves since it is only allowed in the callback.
+    if userdata.control.latency_wanted() {
+        let mut latency: c_double = 0.0;
+        if unsafe { raw::soundio_instream_get_latency(stream, &mut latency) } == 0 {
+            userdata.control.set_latency(latency);
+        }
+    }
 }
 
 pub extern "C" fn instream_overflow_callback(stream: *mut raw::SoundIoInStream) {
@@ -40,10 +65,9 @@ pub extern "C" fn instream_overflow_callback(stream: *mut raw::SoundIoInStream)
     let raw_userdata_pointer = unsafe { (*stream).userdata as *mut InStreamUserData };
     let userdata = unsafe { &mut (*raw_userdata_pointer) };
 
+    userdata.event_sink.record(Event::Overflow);
     if let Some(ref mut cb) = userdata.overflow_callback {
         cb();
-    } else {
-        println!("Overflow!");
     }
 }
 
@@ -52,17 +76,17 @@ pub extern "C" fn instream_error_callback(stream: *mut raw::SoundIoInStream, err
     let raw_userdata_pointer = unsafe { (*stream).userdata as *mut InStreamUserData };
     let userdata = unsafe { &mut (*raw_userdata_pointer) };
 
+    let err = Error::from(err);
+    userdata.event_sink.record(Event::StreamError(err));
     if let Some(ref mut cb) = userdata.error_callback {
-        cb(err.into());
-    } else {
-        println!("Error: {}", Error::from(err));
+        cb(err);
     }
 }
 
 /// InStream represents an input stream for recording.
 ///
-/// It is obtained from `Device` using `Device::open_instream()` and
-/// can be started and paused.
+/// It is obtained from `Device` using `Device::open_instream()` or
+/// `Device::instream_builder()` and can be started and paused.
 pub struct InStream<'a> {
     pub userdata: Box<InStreamUserData<'a>>,
 
@@ -78,11 +102,26 @@ pub struct InStreamUserData<'a> {
     pub read_callback: Box<dyn FnMut(&mut InStreamReader) + 'a>,
     pub overflow_callback: Option<Box<dyn FnMut() + 'a>>,
     pub error_callback: Option<Box<dyn FnMut(Error) + 'a>>,
+
+    // Callback events are recorded here.
+    pub event_sink: Arc<EventSink>,
+
+    // Shared with the stream's handles.
+    pub(crate) control: Arc<StreamControl<raw::SoundIoInStream>>,
+    // Held while the stream is created and destroyed, since that refs and unrefs the
+    // device. See `Context::start_event_thread()`.
+    pub(crate) device_lock: Arc<Mutex<()>>,
+
+    // libsoundio keeps a pointer to the stream name, so we own the string here.
+    pub name: Option<CString>,
 }
 
 impl<'a> Drop for InStreamUserData<'a> {
     fn drop(&mut self) {
         unsafe {
+            // Handles must not use the stream once it is destroyed.
+            self.control.close();
+            let _lock = lock_devices(&self.device_lock);
             raw::soundio_instream_destroy(self.instream);
         }
     }
@@ -110,8 +149,6 @@ impl<'a> InStream<'a> {
         }
     }
 
-    // TODO: Can pause() be called from the read callback?
-
     /// If the underlying backend and device support pausing, this pauses the
     /// stream. The `write_callback()` may be called a few more times if
     /// the buffer is not full.
@@ -128,6 +165,7 @@ impl<'a> InStream<'a> {
     /// * `Error::Streaming`
     /// * `Error::IncompatibleDevice` - device does not support pausing/unpausing
     ///
+    /// To pause from inside the read callback use `InStreamReader::pause()`.
     pub fn pause(&mut self, pause: bool) -> Result<()> {
         match unsafe { raw::soundio_instream_pause(self.userdata.instream, pause as i8) } {
             0 => Ok(()),
@@ -173,7 +211,7 @@ impl<'a> InStream<'a> {
     /// WASAPI uses this for the session display name.
     /// Must not contain a colon (":").
     ///
-    /// TODO: Currently there is no way to set this.
+    /// It can be set when opening the stream using `InStreamBuilder::name()`.
     pub fn name(&self) -> String {
         unsafe { utf8_to_string((*self.userdata.instream).name) }
     }
@@ -188,15 +226,400 @@ impl<'a> InStream<'a> {
     pub fn bytes_per_sample(&self) -> i32 {
         unsafe { (*self.userdata.instream).bytes_per_sample as _ }
     }
+
+    /// The sink that events from this stream's callbacks are recorded in.
+    pub fn event_sink(&self) -> &Arc<EventSink> {
+        &self.userdata.event_sink
+    }
+
+    /// Get a handle that can control the stream from other threads. See `InStreamHandle`.
+    pub fn handle(&self) -> InStreamHandle {
+        InStreamHandle::new(self.userdata.control.clone())
+    }
+}
+
+type ReadCallback<'a> = Box<dyn FnMut(&mut InStreamReader) + 'a>;
+
+/// `InStreamBuilder` is used to configure and open an `InStream`. It is obtained
+/// from `Device::instream_builder()`.
+///
+/// Every parameter is optional except the read callback. Parameters that are not
+/// set default to the device's current settings (`Device::current_sample_rate()`,
+/// `Device::current_format()` and `Device::current_layout()`) if they are known,
+/// and otherwise to libsoundio's defaults.
+///
+/// As with `OutStreamBuilder`, the sample rate, format and layout are checked against
+/// the device before the stream is opened, so you get an error saying which one is
+/// unsupported rather than `Error::IncompatibleDevice`.
+///
+/// # Examples
+///
+/// ```
+/// # fn foo() -> Result<(), soundio::Error> {
+/// let mut ctx = soundio::Context::new();
+/// ctx.connect_backend(soundio::Backend::Dummy)?;
+/// let dev = ctx.default_input_device()?;
+///
+/// let mut stream = dev
+///     .instream_builder()
+///     .sample_rate(44100)
+///     .software_latency(0.1)
+///     .name("Recorder")
+///     .read_callback(|stream: &mut soundio::InStreamReader| {
+///         let frame_count_max = stream.frame_count_max();
+///         stream.begin_read(frame_count_max).unwrap();
+///     })
+///     .overflow_callback(|| println!("Overflow"))
+///     .open()?;
+///
+/// stream.start()?;
+/// # Ok(())
+/// # }
+/// ```
+pub struct InStreamBuilder<'a, 'b> {
+    device: &'a Device<'a>,
+
+    sample_rate: Option<i32>,
+    format: Option<Format>,
+    layout: Option<ChannelLayout>,
+    software_latency: Option<f64>,
+    name: Option<String>,
+    non_terminal_hint: bool,
+
+    read_callback: Option<ReadCallback<'b>>,
+    overflow_callback: Option<Box<dyn FnMut() + 'b>>,
+    error_callback: Option<Box<dyn FnMut(Error) + 'b>>,
+    event_sink: Option<Arc<EventSink>>,
+}
+
+impl<'a, 'b: 'a> InStreamBuilder<'a, 'b> {
+    /// Create a builder for the given device. Prefer `Device::instream_builder()`.
+    pub fn new(device: &'a Device<'a>) -> InStreamBuilder<'a, 'b> {
+        let sample_rate = device.current_sample_rate();
+        let format = device.current_format();
+        let layout = device.current_layout();
+
+        InStreamBuilder {
+            device,
+            sample_rate: if sample_rate > 0 {
+                Some(sample_rate)
+            } else {
+                None
+            },
+            format: if format != Format::Invalid {
+                Some(format)
+            } else {
+                None
+            },
+            layout: if !layout.channels.is_empty() {
+                Some(layout)
+            } else {
+                None
+            },
+            software_latency: None,
+            name: None,
+            non_terminal_hint: false,
+            read_callback: None,
+            overflow_callback: None,
+            error_callback: None,
+            event_sink: None,
+        }
+    }
+
+    /// The requested sample rate. Check supported sample rates first with `Device::sample_rates()`.
+    pub fn sample_rate(mut self, sample_rate: i32) -> Self {
+        self.sample_rate = Some(sample_rate);
+        self
+    }
+
+    /// The requested format. Check supported formats first with `Device::formats()`.
+    pub fn format(mut self, format: Format) -> Self {
+        self.format = Some(format);
+        self
+    }
+
+    /// The requested channel layout. Check supported layouts first with `Device::layouts()`.
+    pub fn layout(mut self, layout: ChannelLayout) -> Self {
+        self.layout = Some(layout);
+        self
+    }
+
+    /// Set the sample rate, format and layout at once, for example from
+    /// `Device::negotiate_config()`.
+    pub fn config(self, config: StreamConfig) -> Self {
+        self.sample_rate(config.sample_rate)
+            .format(config.format)
+            .layout(config.layout)
+    }
+
+    /// The requested software latency in seconds. With a lower value your read callback
+    /// will be called more often and work in smaller blocks but latency will be lower.
+    ///
+    /// See `InStream::software_latency()` for more details. If this is not set
+    /// the backend chooses a default.
+    pub fn software_latency(mut self, latency: f64) -> Self {
+        self.software_latency = Some(latency);
+        self
+    }
+
+    /// The name of the stream. PulseAudio uses this for the stream name,
+    /// JACK uses it for the client name and WASAPI uses it for the session
+    /// display name. Any colons are removed. The default is "SoundIoInStream".
+    pub fn name(mut self, name: &str) -> Self {
+        self.name = Some(name.chars().filter(|&x| x != ':' && x != '\0').collect());
+        self
+    }
+
+    /// Hint that this input stream is nonterminal. This is used by JACK and it
+    /// means that the data received by the stream will be passed on or made
+    /// available to another stream. Defaults to `false`.
+    pub fn non_terminal_hint(mut self, non_terminal_hint: bool) -> Self {
+        self.non_terminal_hint = non_terminal_hint;
+        self
+    }
+
+    /// Required callback that is called to allow you to read audio data from the instream.
+    /// See `InStreamReader` for more details.
+    pub fn read_callback<ReadCB>(mut self, read_callback: ReadCB) -> Self
+    where
+        ReadCB: 'b + FnMut(&mut InStreamReader),
+    {
+        self.read_callback = Some(Box::new(read_callback));
+        self
+    }
+
+    /// Optional callback that is called when your read callback is too slow and some input is skipped.
+    pub fn overflow_callback<OverflowCB>(mut self, overflow_callback: OverflowCB) -> Self
+    where
+        OverflowCB: 'b + FnMut(),
+    {
+        self.overflow_callback = Some(Box::new(overflow_callback));
+        self
+    }
+
+    /// Optional error callback. The error is always `Error::Streaming`, which
+    /// means the stream is in an invalid state and must be destroyed.
+    pub fn error_callback<ErrorCB>(mut self, error_callback: ErrorCB) -> Self
+    where
+        ErrorCB: 'b + FnMut(Error),
+    {
+        self.error_callback = Some(Box::new(error_callback));
+        self
+    }
+
+    /// The sink that overflows, errors and failures to end a read are recorded in.
+    /// By default each stream has its own sink that only counts events. See `EventSink`.
+    pub fn event_sink(mut self, event_sink: Arc<EventSink>) -> Self {
+        self.event_sink = Some(event_sink);
+        self
+    }
+
+    /// Open the input stream. After opening you can start, pause and stop it
+    /// using the functions on the `InStream` that is returned. Then your read callback
+    /// will be called.
+    ///
+    /// # Errors
+    ///
+    /// * `Error::UnsupportedSampleRate` - the device does not support the sample rate.
+    /// * `Error::UnsupportedFormat` - the device does not support the format.
+    /// * `Error::UnsupportedLayout` - the device does not support the channel layout.
+    /// * `Error::Invalid`
+    ///   - no read callback was set
+    ///   - `Device::aim()` is not `DeviceAim::Input`
+    ///   - `channel_count` is greater than `SOUNDIO_MAX_CHANNELS` (24).
+    /// * `Error::NoMem`
+    /// * `Error::OpeningDevice`
+    /// * `Error::BackendDisconnected`
+    /// * `Error::SystemResources`
+    /// * `Error::NoSuchClient` - when JACK returns `JackNoSuchClient`
+    /// * `Error::IncompatibleBackend` - `InStream` channel count is greater than the number of channels the backend can handle.
+    /// * `Error::IncompatibleDevice` - stream parameters requested are not compatible with the chosen device.
+    pub fn open(self) -> Result<InStream<'b>> {
+        let read_callback = match self.read_callback {
+            Some(cb) => cb,
+            None => return Err(Error::Invalid),
+        };
+
+        if let Some(sample_rate) = self.sample_rate {
+            if !self.device.supports_sample_rate(sample_rate) {
+                return Err(Error::UnsupportedSampleRate);
+            }
+        }
+        if let Some(format) = self.format {
+            if !self.device.supports_format(format) {
+                return Err(Error::UnsupportedFormat);
+            }
+        }
+        if let Some(ref layout) = self.layout {
+            if !self.device.supports_layout(layout.clone()) {
+                return Err(Error::UnsupportedLayout);
+            }
+        }
+
+        let instream = {
+            let _lock = lock_devices(&self.device.device_lock);
+            unsafe { raw::soundio_instream_create(self.device.device) }
+        };
+        if instream.is_null() {
+            // Note that we should really abort() here (that's what the rest of Rust
+            // does on OOM), but there is no stable way to abort in Rust that I can see.
+            panic!("soundio_instream_create() failed (out of memory).");
+        }
+
+        let name = self
+            .name
+            .map(|n| CString::new(n).expect("Stream name contains a null byte"));
+
+        unsafe {
+            // Anything that isn't set is left at libsoundio's default.
+            if let Some(sample_rate) = self.sample_rate {
+                (*instream).sample_rate = sample_rate;
+            }
+            if let Some(format) = self.format {
+                (*instream).format = format.into();
+            }
+            if let Some(layout) = self.layout {
+                (*instream).layout = layout.into();
+            }
+            if let Some(latency) = self.software_latency {
+                (*instream).software_latency = latency;
+            }
+            if let Some(ref name) = name {
+                (*instream).name = name.as_ptr();
+            }
+            (*instream).non_terminal_hint = self.non_terminal_hint as i8;
+            (*instream).read_callback = instream_read_callback;
+            (*instream).overflow_callback = Some(instream_overflow_callback);
+            (*instream).error_callback = Some(instream_error_callback);
+        }
+
+        let mut stream = InStream {
+            userdata: Box::new(InStreamUserData {
+                instream,
+                read_callback,
+                overflow_callback: self.overflow_callback,
+                error_callback: self.error_callback,
+                event_sink: self
+                    .event_sink
+                    .unwrap_or_else(|| Arc::new(EventSink::new())),
+                control: Arc::new(StreamControl::new(instream)),
+                device_lock: self.device.device_lock.clone(),
+                name,
+            }),
+            phantom: PhantomData,
+        };
+
+        // Safe userdata pointer.
+        unsafe {
+            (*stream.userdata.instream).userdata =
+                stream.userdata.as_mut() as *mut InStreamUserData as *mut _;
+        }
+
+        match unsafe { raw::soundio_instream_open(stream.userdata.instream) } {
+            0 => {}
+            x => return Err(x.into()),
+        };
+
+        match unsafe { (*stream.userdata.instream).layout_error } {
+            0 => {}
+            x => return Err(x.into()),
+        }
+
+        Ok(stream)
+    }
+
+    /// Open the input stream as a `TypedInStream`, whose format is `T::FORMAT`.
+    /// `read_callback` is called with a `TypedInStreamReader` instead of an `InStreamReader`,
+    /// so it gets `T` samples directly. Any read callback that was set is ignored.
+    ///
+    /// # Errors
+    ///
+    /// * `Error::UnsupportedFormat` - the device doesn't support `T::FORMAT`.
+    /// * The errors returned by `open()`.
+    pub fn open_typed<T, F>(self, mut read_callback: F) -> Result<TypedInStream<'b, T>>
+    where
+        T: NativeSample,
+        F: 'b + FnMut(&mut TypedInStreamReader<T>),
+    {
+        let stream = self
+            .format(T::FORMAT)
+            .read_callback(move |stream: &mut InStreamReader| {
+                read_callback(&mut TypedInStreamReader::new(stream))
+            })
+            .open()?;
+        Ok(TypedInStream::new(stream))
+    }
+
+    /// Open the input stream as a `BlockingInStream`, which you read from instead of
+    /// supplying a read callback. Any read callback that was set is ignored, and the
+    /// format is set to `T::FORMAT`. The overflow and error callbacks are still called.
+    ///
+    /// `buffer_duration` is the amount of audio in seconds that is kept until it is read
+    /// with `BlockingInStream::read()`. The stream is started immediately.
+    ///
+    /// # Errors
+    ///
+    /// The same as `open()` and `InStream::start()`, and `Error::NoMem` if the
+    /// ring buffer could not be created.
+    pub fn open_blocking<T: NativeSample + 'static>(
+        mut self,
+        buffer_duration: f64,
+    ) -> Result<BlockingInStream<'b, T>> {
+        let device = self.device;
+        let shared = Arc::new(BlockingShared::new());
+        let (handoff, mut producer) = handoff();
+
+        let cb_shared = shared.clone();
+        let mut overflow_callback = self.overflow_callback.take();
+        self.overflow_callback = Some(Box::new(move || {
+            cb_shared.xrun();
+            if let Some(ref mut cb) = overflow_callback {
+                cb();
+            }
+        }));
+
+        let cb_shared = shared.clone();
+        let mut error_callback = self.error_callback.take();
+        self.error_callback = Some(Box::new(move |err| {
+            cb_shared.set_errored();
+            if let Some(ref mut cb) = error_callback {
+                cb(err);
+            }
+        }));
+
+        let cb_shared = shared.clone();
+        let stream = self
+            .format(T::FORMAT)
+            .read_callback(move |stream: &mut InStreamReader| {
+                if let Some(producer) = producer.get() {
+                    blocking_read_callback::<T>(stream, producer, &cb_shared)
+                }
+            })
+            .open()?;
+
+        // The stream might not have the sample rate and layout that were asked for, so
+        // the buffer is sized once it is open.
+        let channel_count = unsafe { (*stream.userdata.instream).layout.channel_count as usize };
+        let ring_buffer = RingBuffer::for_device(
+            device,
+            ring_buffer_size::<T>(buffer_duration, stream.sample_rate(), channel_count),
+        )?;
+        let (producer, consumer) = ring_buffer.split();
+        handoff.send(producer);
+
+        BlockingInStream::new(stream, consumer, shared)
+    }
 }
 
 /// `InStreamReader` is passed to the read callback and can be used to read from the stream.
 ///
 /// You start by calling `begin_read()` and then you can read the samples. When the `InStreamReader`
-/// is dropped the samples are dropped. An error at that point is written to the console and ignored.
+/// is dropped the samples are dropped. An error at that point is recorded in the stream's `EventSink`.
 ///
 pub struct InStreamReader<'a> {
     instream: *mut raw::SoundIoInStream,
+    event_sink: &'a EventSink,
+    control: &'a StreamControl<raw::SoundIoInStream>,
     frame_count_min: usize,
     frame_count_max: usize,
 
@@ -212,10 +635,13 @@ pub struct InStreamReader<'a> {
 }
 
 impl<'a> InStreamReader<'a> {
-    /// Start a read. You can only call this once per callback otherwise it panics.
+    /// Start a read. If the returned frame count is less than you wanted you can
+    /// call `end_read()` and then `begin_read()` again to read the rest.
     ///
     /// frame_count is the number of frames you want to read. It must be between
     /// frame_count_min and frame_count_max inclusive, or `begin_read()` will panic.
+    /// After each read `frame_count_min()` and `frame_count_max()` are reduced by the
+    /// number of frames read.
     ///
     /// It returns the number of frames you can actually read. The returned value
     /// will always be less than or equal to the provided value.
@@ -248,18 +674,19 @@ impl<'a> InStreamReader<'a> {
             0 => {
                 self.read_started = true;
                 self.frame_count = actual_frame_count as _;
+                self.frame_count_min = self.frame_count_min.saturating_sub(self.frame_count);
+                self.frame_count_max -= self.frame_count;
                 // Return now if there's no frames to actually read.
                 if actual_frame_count <= 0 {
                     return Ok(0);
                 }
                 let cc = self.channel_count();
-                self.channel_areas = vec![
-                    raw::SoundIoChannelArea {
-                        ptr: ptr::null_mut(),
-                        step: 0
-                    };
-                    cc
-                ];
+                self.channel_areas = vec![NO_AREA; cc];
+                // A null areas pointer means there is a hole: the frames were dropped. The
+                // areas are left null, which `is_hole()` checks.
+                if areas.is_null() {
+                    return Ok(actual_frame_count as _);
+                }
                 unsafe {
                     self.channel_areas.copy_from_slice(slice::from_raw_parts::<
                         raw::SoundIoChannelArea,
@@ -271,33 +698,36 @@ impl<'a> InStreamReader<'a> {
         }
     }
 
-    /// Commits the write that you began with `begin_read()`.
+    /// Ends the read that you began with `begin_read()`, dropping the frames. This does
+    /// nothing if there is no read in progress.
     ///
-    /// Errors are currently are just printed to the console and ignored.
+    /// If you don't call this it is called when the `InStreamReader` is dropped, and
+    /// any error is recorded in the stream's `EventSink` instead of being returned.
     ///
     /// # Errors
     ///
     /// * `Error::Streaming`
-    /// * `Error::Underflow` - an underflow caused this call to fail. You might
-    ///   also get an `underflow_callback()`, and you might not get
-    ///   this error code when an underflow occurs. Unlike `Error::Streaming`,
-    ///   the outstream is still in a valid state and streaming can continue.
-    pub fn end_read(&mut self) {
-        if self.read_started {
-            unsafe {
-                match raw::soundio_instream_end_read(self.instream) {
-                    0 => {
-                        self.read_started = false;
-                    }
-                    x => println!("Error ending instream: {}", Error::from(x)),
-                }
+    pub fn end_read(&mut self) -> Result<()> {
+        if !self.read_started {
+            return Ok(());
+        }
+        // The read is over even if it failed, so don't end it again in `drop()`.
+        self.read_started = false;
+        match unsafe { raw::soundio_instream_end_read(self.instream) } {
+            0 => {
+                self.control.advance(self.frame_count);
+                Ok(())
             }
+            e => Err(e.into()),
         }
     }
 
     /// Get the minimum frame count that you can call `begin_read()` with.
     /// Retreive this value before calling `begin_read()` to ensure you read the correct number
     /// of frames.
+    ///
+    /// This is reduced by the number of frames read after each `begin_read()`, so it
+    /// is what is left for the rest of the callback rather than the value it started with.
     pub fn frame_count_min(&self) -> usize {
         self.frame_count_min
     }
@@ -305,10 +735,67 @@ impl<'a> InStreamReader<'a> {
     /// Get the maximum frame count that you can call `begin_read()` with.
     /// Retreive this value before calling `begin_read()` to ensure you read the correct number
     /// of frames.
+    ///
+    /// This is reduced by the number of frames read after each `begin_read()`, so it
+    /// is what is left for the rest of the callback rather than the value it started with.
     pub fn frame_count_max(&self) -> usize {
         self.frame_count_max
     }
 
+    /// Returns true if the backend dropped the frames of the current read, for example
+    /// because of an overflow. There are `frame_count()` frames but no samples to read,
+    /// so you should usually treat them as silence. This is only possible on some
+    /// backends.
+    ///
+    /// The functions that read samples panic or return `SliceError::Hole` for a hole.
+    /// Panics if you haven't called `begin_read()` yet.
+    pub fn is_hole(&self) -> bool {
+        assert!(self.read_started);
+        self.frame_count > 0 && self.channel_areas.iter().any(|a| a.ptr.is_null())
+    }
+
+    /// Returns true if `request_stop()` has been called here or on one of the stream's
+    /// handles. See `InStreamHandle`.
+    pub fn stop_requested(&self) -> bool {
+        self.control.stop_requested()
+    }
+
+    /// Set the "stop requested" flag, for example to tell the thread that owns the stream
+    /// that the end of the audio has been reached. It can be checked with
+    /// `InStreamHandle::stop_requested()`.
+    pub fn request_stop(&self) {
+        self.control.request_stop()
+    }
+
+    /// The channel areas of the current read, which are empty until `begin_read()`.
+    pub(crate) fn channel_areas(&self) -> &[raw::SoundIoChannelArea] {
+        &self.channel_areas
+    }
+
+    /// The position of the current read in the stream: the number of frames that were
+    /// read before it since the stream was opened, including holes. If there is no read
+    /// in progress it is the position of the next one.
+    pub fn frame_position(&self) -> u64 {
+        self.control.frame_position()
+    }
+
+    /// The position of the current read in the stream together with the estimated time
+    /// at which its first frame was captured, which is now minus `get_latency()`. With
+    /// `StreamTimestamp::time_of()` you can get the capture time of any frame, for
+    /// example to align the audio with other sensors.
+    ///
+    /// # Errors
+    ///
+    /// * `Error::Streaming`
+    pub fn timestamp(&mut self) -> Result<StreamTimestamp> {
+        let latency = self.get_latency()?;
+        Ok(timestamp_from_now(
+            self.frame_position(),
+            -latency,
+            self.sample_rate(),
+        ))
+    }
+
     /// Get the actual frame count that you did call `begin_read()` with. Panics if you haven't called
     /// `begin_read()` yet.
     pub fn frame_count(&self) -> usize {
@@ -331,6 +818,11 @@ impl<'a> InStreamReader<'a> {
         unsafe { (*self.instream).sample_rate as _ }
     }
 
+    /// Get the format of the stream.
+    pub fn format(&self) -> Format {
+        unsafe { (*self.instream).format.into() }
+    }
+
     /// Obtain the number of seconds that the next frame of sound being
     /// captured will take to arrive in the buffer, plus the amount of time that is
     /// represented in the buffer. This includes both software and hardware latency.
@@ -347,8 +839,27 @@ impl<'a> InStreamReader<'a> {
         }
     }
 
+    /// Pause or unpause the stream from inside the read callback. When paused the read
+    /// callback is not called any more. See `InStream::pause()` for details.
+    ///
+    /// Input streams cannot clear their buffer; to discard captured frames just read
+    /// them without using the samples.
+    ///
+    /// # Errors
+    ///
+    /// * `Error::BackendDisconnected`
+    /// * `Error::Streaming`
+    /// * `Error::IncompatibleDevice` - device does not support pausing/unpausing
+    pub fn pause(&mut self, pause: bool) -> Result<()> {
+        match unsafe { raw::soundio_instream_pause(self.instream, pause as i8) } {
+            0 => Ok(()),
+            e => Err(e.into()),
+        }
+    }
+
     /// Get the value of a sample. This panics if the `channel` or `frame` are
-    /// out of range or if you haven't called `begin_read()` yet.
+    /// out of range, if you haven't called `begin_read()` yet, or if the read
+    /// is a hole (see `is_hole()`).
     ///
     /// If you request a different type from the actual one it will be converted.
     ///
@@ -368,6 +879,7 @@ impl<'a> InStreamReader<'a> {
     /// ```
     pub fn sample<T: Sample>(&self, channel: usize, frame: usize) -> T {
         assert!(self.read_started);
+        assert!(!self.is_hole(), "Captured data has a hole");
 
         assert!(channel < self.channel_count(), "Channel out of range");
         assert!(frame < self.frame_count(), "Frame out of range");
@@ -402,25 +914,402 @@ impl<'a> InStreamReader<'a> {
         }
     }
 
-    // TODO: To acheive speed *and* safety I can use iterators. That will be in a future API.
+    /// Get the samples of one channel as a slice of `frame_count()` samples. This is
+    /// much faster than calling `sample()` for every sample, but it is only possible
+    /// if `T` is the stream format (see `NativeSample`) and the samples of the channel
+    /// are next to each other in memory. Otherwise the reason is returned. In that case
+    /// `channel_view()` may still work.
+    ///
+    /// Panics if `channel` is out of range.
+    pub fn channel_slice<T: NativeSample>(
+        &self,
+        channel: usize,
+    ) -> result::Result<&[T], SliceError> {
+        if !self.read_started {
+            return Err(SliceError::NotStarted);
+        }
+        assert!(channel < self.channel_count(), "Channel out of range");
+        if self.frame_count == 0 {
+            return Ok(&[]);
+        }
+
+        let ptr = channel_ptr::<T>(&self.channel_areas, self.format(), channel)?;
+        unsafe { Ok(slice::from_raw_parts(ptr, self.frame_count)) }
+    }
+
+    /// Get the samples of all channels as a single interleaved slice of
+    /// `frame_count() * channel_count()` samples, i.e. LRLRLR for stereo.
+    ///
+    /// This is only possible if `T` is the stream format (see `NativeSample`) and
+    /// the backend uses an interleaved buffer. Otherwise the reason is returned.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// fn read_callback(stream: &mut soundio::InStreamReader, buffer: &mut Vec<f32>) {
+    ///     let frame_count_max = stream.frame_count_max();
+    ///     stream.begin_read(frame_count_max).unwrap();
+    ///     match stream.interleaved_slice::<f32>() {
+    ///         Ok(samples) => buffer.extend_from_slice(samples),
+    ///         Err(e) => println!("Can't read samples: {}", e),
+    ///     }
+    /// }
+    /// ```
+    pub fn interleaved_slice<T: NativeSample>(&self) -> result::Result<&[T], SliceError> {
+        if !self.read_started {
+            return Err(SliceError::NotStarted);
+        }
+        if self.frame_count == 0 {
+            return Ok(&[]);
+        }
+
+        let ptr = interleaved_ptr::<T>(&self.channel_areas, self.format())?;
+        unsafe {
+            Ok(slice::from_raw_parts(
+                ptr,
+                self.frame_count * self.channel_areas.len(),
+            ))
+        }
+    }
+
+    /// Get a view of the samples of one channel. Unlike `channel_slice()` this works
+    /// when the samples are not next to each other in memory (e.g. for interleaved
+    /// buffers), but `T` must still be the stream format.
+    ///
+    /// Panics if `channel` is out of range.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// fn read_callback(stream: &mut soundio::InStreamReader) {
+    ///     let frame_count_max = stream.frame_count_max();
+    ///     stream.begin_read(frame_count_max).unwrap();
+    ///     if let Ok(left) = stream.channel_view::<i16>(0) {
+    ///         let peak = left.iter().map(|s| (s as i32).abs()).max();
+    ///         println!("Peak: {:?}", peak);
+    ///     }
+    /// }
+    /// ```
+    pub fn channel_view<T: NativeSample>(
+        &self,
+        channel: usize,
+    ) -> result::Result<ChannelView<'_, T>, SliceError> {
+        if !self.read_started {
+            return Err(SliceError::NotStarted);
+        }
+        assert!(channel < self.channel_count(), "Channel out of range");
+        if self.frame_count == 0 {
+            return unsafe { Ok(ChannelView::new(ptr::null(), 0, 0)) };
+        }
+
+        let (ptr, step) = strided_ptr::<T>(&self.channel_areas, self.format(), channel)?;
+        unsafe { Ok(ChannelView::new(ptr, step, self.frame_count)) }
+    }
+
+    /// Iterate over the frames of the read. Each frame lets you get the sample of
+    /// every channel, converted to `T`. The conversion function is chosen once when
+    /// this is called, so it is faster than `sample()`, though not as fast as
+    /// `channel_slice()`. Panics if you haven't called `begin_read()` yet, or if
+    /// the backend reported a hole in the captured data.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// fn read_callback(stream: &mut soundio::InStreamReader) {
+    ///     let frame_count_max = stream.frame_count_max();
+    ///     stream.begin_read(frame_count_max).unwrap();
+    ///     for frame in stream.frames::<f32>() {
+    ///         let mono: f32 = (0..frame.channel_count()).map(|c| frame.get(c)).sum();
+    ///         do_something_with(mono);
+    ///     }
+    /// }
+    /// # fn do_something_with(_: f32) { }
+    /// ```
+    pub fn frames<T: Sample>(&self) -> Frames<'_, T> {
+        assert!(self.read_started);
+        assert!(
+            self.channel_areas.iter().all(|a| !a.ptr.is_null()),
+            "Captured data has a hole"
+        );
+        unsafe { Frames::new(&self.channel_areas, self.format(), self.frame_count) }
+    }
+
+    /// Iterate over the samples of one channel, converted to `T`. Panics if `channel`
+    /// is out of range, if you haven't called `begin_read()` yet, or if the backend
+    /// reported a hole in the captured data.
+    pub fn channel_samples<T: Sample>(&self, channel: usize) -> ChannelSamples<'_, T> {
+        assert!(self.read_started);
+        assert!(channel < self.channel_count(), "Channel out of range");
+        if self.frame_count == 0 {
+            return unsafe { ChannelSamples::new(&NO_AREA, self.format(), 0) };
+        }
+        assert!(
+            !self.channel_areas[channel].ptr.is_null(),
+            "Captured data has a hole"
+        );
+        unsafe {
+            ChannelSamples::new(
+                &self.channel_areas[channel],
+                self.format(),
+                self.frame_count,
+            )
+        }
+    }
+
+    /// Read `frame_count` frames, calling `chunk_fn` for each contiguous chunk of the
+    /// input buffer. This does the `begin_read()` and `end_read()` loop for you: the
+    /// backend might not be able to give you all of the frames at once, in which case
+    /// `chunk_fn` is called several times. It is passed a `ReadChunk` that lets you read
+    /// the samples as `T`, and the offset of the chunk's first frame from the start of
+    /// the read.
+    ///
+    /// A chunk may be a hole, where the backend dropped frames. Check
+    /// `ReadChunk::is_hole()` and use `ReadChunk::frame_count()` to find out how many
+    /// frames of silence to insert.
+    ///
+    /// `frame_count` must be between `frame_count_min()` and `frame_count_max()` or this
+    /// panics. Returns the total number of frames read, which may be less than
+    /// `frame_count` if the backend returned no frames.
+    ///
+    /// # Errors
+    ///
+    /// The same as `begin_read()` and `end_read()`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// fn read_callback(stream: &mut soundio::InStreamReader, buffer: &mut Vec<f32>) {
+    ///     let frame_count_max = stream.frame_count_max();
+    ///     let result = stream.read_chunks::<f32, _>(frame_count_max, |chunk, _offset| {
+    ///         if chunk.is_hole() {
+    ///             let len = buffer.len();
+    ///             buffer.resize(len + chunk.frame_count() * chunk.channel_count(), 0.0);
+    ///         } else {
+    ///             for frame in chunk.frames() {
+    ///                 for c in 0..frame.channel_count() {
+    ///                     buffer.push(frame.get(c));
+    ///                 }
+    ///             }
+    ///         }
+    ///     });
+    ///     if let Err(e) = result {
+    ///         println!("Error reading from stream: {}", e);
+    ///     }
+    /// }
+    /// ```
+    pub fn read_chunks<T, F>(&mut self, frame_count: usize, mut chunk_fn: F) -> Result<usize>
+    where
+        T: Sample,
+        F: FnMut(ReadChunk<'_, 'a, T>, usize),
+    {
+        let mut offset = 0;
+        while offset < frame_count {
+            let chunk_frames = self.begin_read(frame_count - offset)?;
+            if chunk_frames == 0 {
+                self.end_read()?;
+                break;
+            }
+            chunk_fn(
+                ReadChunk {
+                    reader: self,
+                    offset,
+                    phantom: PhantomData,
+                },
+                offset,
+            );
+            self.end_read()?;
+            offset += chunk_frames;
+        }
+        Ok(offset)
+    }
+
+    /// Read `frame_count` frames one chunk at a time. This is like `read_chunks()`,
+    /// but instead of a closure you call `ReadChunks::next_chunk()` in a loop, which
+    /// ends the previous chunk and begins the next one.
+    ///
+    /// `frame_count` must be between `frame_count_min()` and `frame_count_max()` or the
+    /// first call to `next_chunk()` panics.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// fn read_callback(stream: &mut soundio::InStreamReader, lost_frames: &mut usize) {
+    ///     let frame_count_max = stream.frame_count_max();
+    ///     let mut chunks = stream.chunks::<f32>(frame_count_max);
+    ///     while let Some(chunk) = chunks.next_chunk() {
+    ///         match chunk {
+    ///             Ok(ref chunk) if chunk.is_hole() => *lost_frames += chunk.frame_count(),
+    ///             Ok(chunk) => {
+    ///                 for frame in chunk.frames() {
+    ///                     do_something_with(frame.get(0));
+    ///                 }
+    ///             }
+    ///             Err(e) => {
+    ///                 println!("Error reading from stream: {}", e);
+    ///                 break;
+    ///             }
+    ///         }
+    ///     }
+    /// }
+    /// # fn do_something_with(_: f32) { }
+    /// ```
+    pub fn chunks<T: Sample>(&mut self, frame_count: usize) -> ReadChunks<'_, 'a, T> {
+        ReadChunks {
+            reader: self,
+            frame_count,
+            offset: 0,
+            done: false,
+            phantom: PhantomData,
+        }
+    }
+}
+
+/// Reads the chunks of an input buffer one by one. It is returned by
+/// `InStreamReader::chunks()`.
+///
+/// This is not an `Iterator` because each chunk borrows the reader, and must be
+/// finished with before the next one begins.
+pub struct ReadChunks<'r, 'a: 'r, T> {
+    reader: &'r mut InStreamReader<'a>,
+    frame_count: usize,
+    offset: usize,
+    done: bool,
+    phantom: PhantomData<T>,
+}
+
+impl<'r, 'a, T: Sample> ReadChunks<'r, 'a, T> {
+    /// End the previous chunk, if any, and begin the next one. Returns `None` when all
+    /// of the frames have been read, or after an error.
+    pub fn next_chunk(&mut self) -> Option<Result<ReadChunk<'_, 'a, T>>> {
+        if self.done {
+            return None;
+        }
+        if let Err(e) = self.reader.end_read() {
+            self.done = true;
+            return Some(Err(e));
+        }
+        if self.offset >= self.frame_count {
+            self.done = true;
+            return None;
+        }
+
+        let chunk_frames = match self.reader.begin_read(self.frame_count - self.offset) {
+            Ok(n) => n,
+            Err(e) => {
+                self.done = true;
+                return Some(Err(e));
+            }
+        };
+        if chunk_frames == 0 {
+            self.done = true;
+            return match self.reader.end_read() {
+                Ok(()) => None,
+                Err(e) => Some(Err(e)),
+            };
+        }
+
+        let offset = self.offset;
+        self.offset += chunk_frames;
+        Some(Ok(ReadChunk {
+            reader: self.reader,
+            offset,
+            phantom: PhantomData,
+        }))
+    }
+
+    /// The number of frames read so far, including holes.
+    pub fn frames_read(&self) -> usize {
+        self.offset
+    }
+}
+
+/// One contiguous chunk of an input buffer, passed to the closure given to
+/// `InStreamReader::read_chunks()` or returned by `ReadChunks::next_chunk()`. Samples are read as `T`, converted from the
+/// stream format if necessary. Frame indices are relative to the start of the chunk.
+///
+/// If the chunk is a hole (see `is_hole()`) only `frame_count()` and `channel_count()`
+/// are meaningful and the functions that access the samples panic or return
+/// `SliceError::Hole`.
+pub struct ReadChunk<'c, 'a: 'c, T> {
+    reader: &'c InStreamReader<'a>,
+    offset: usize,
+    phantom: PhantomData<T>,
+}
+
+impl<'c, 'a, T: Sample> ReadChunk<'c, 'a, T> {
+    /// The number of frames in this chunk.
+    pub fn frame_count(&self) -> usize {
+        self.reader.frame_count
+    }
+
+    /// The number of channels in the stream.
+    pub fn channel_count(&self) -> usize {
+        self.reader.channel_count()
+    }
+
+    /// The sample rate in Hertz.
+    pub fn sample_rate(&self) -> i32 {
+        self.reader.sample_rate()
+    }
+
+    /// Returns true if the backend dropped these frames, so there are no samples
+    /// to read. The frames should usually be treated as silence.
+    pub fn is_hole(&self) -> bool {
+        self.reader.is_hole()
+    }
+
+    /// The offset of the first frame of this chunk from the start of the read.
+    pub fn offset(&self) -> usize {
+        self.offset
+    }
+
+    /// Get a sample. See `InStreamReader::sample()`.
+    pub fn get(&self, channel: usize, frame: usize) -> T {
+        self.reader.sample(channel, frame)
+    }
+
+    /// Iterate over the frames of the chunk. See `InStreamReader::frames()`.
+    pub fn frames(&self) -> Frames<'c, T> {
+        self.reader.frames()
+    }
+
+    /// Iterate over the samples of one channel. See `InStreamReader::channel_samples()`.
+    pub fn channel_samples(&self, channel: usize) -> ChannelSamples<'c, T> {
+        self.reader.channel_samples(channel)
+    }
+}
+
+impl<'c, 'a, T: NativeSample> ReadChunk<'c, 'a, T> {
+    /// Get the samples of one channel as a slice. See `InStreamReader::channel_slice()`.
+    pub fn channel_slice(&self, channel: usize) -> result::Result<&'c [T], SliceError> {
+        self.reader.channel_slice(channel)
+    }
+
+    /// Get the samples of all channels as an interleaved slice. See
+    /// `InStreamReader::interleaved_slice()`.
+    pub fn interleaved_slice(&self) -> result::Result<&'c [T], SliceError> {
+        self.reader.interleaved_slice()
+    }
+
+    /// Get a view of the samples of one channel. See `InStreamReader::channel_view()`.
+    pub fn channel_view(&self, channel: usize) -> result::Result<ChannelView<'c, T>, SliceError> {
+        self.reader.channel_view(channel)
+    }
 }
 
 impl<'a> Drop for InStreamReader<'a> {
-    /// This will drop all of the frames from when you called `begin_read()`.
+    /// This will drop all of the frames from when you called `begin_read()`, if you
+    /// didn't call `end_read()`.
     ///
-    /// Errors are currently are just printed to the console and ignored.
+    /// Errors are recorded in the stream's `EventSink` as `Event::EndReadError`, and
+    /// can be retrieved with `EventSink::take_last_end_error()`.
     ///
     /// # Errors
     ///
     /// * `Error::Streaming`
     fn drop(&mut self) {
-        if self.read_started {
-            unsafe {
-                match raw::soundio_instream_end_read(self.instream) {
-                    0 => {}
-                    x => println!("Error reading instream: {}", Error::from(x)),
-                }
-            }
+        if let Err(e) = self.end_read() {
+            self.event_sink.record(Event::EndReadError(e));
         }
     }
 }
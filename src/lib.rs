@@ -131,9 +131,12 @@
 //! input_stream.start()?;
 //! ```
 //!
-//! There are some extra details regarding `Context::wait_events()` and `Context::wakeup()`, and you
-//! will likely want to use scoped threads via the `crossbeam` crate for those. The best way to learn
-//! more is to see the examples.
+//! There are some extra details regarding `Context::wait_events()` and `Context::wakeup()`. The
+//! simplest way to handle them is `Context::start_event_thread()`, which waits for events on a
+//! thread owned by the `Context`. Otherwise you will likely want to use scoped threads via the
+//! `crossbeam` crate, with a `ContextWaker` to wake up the `Context` from other threads. With the
+//! `futures` feature, events can also be awaited from an async task with `EventStream`. The best
+//! way to learn more is to see the examples.
 //!
 //! # Examples
 //!
@@ -170,32 +173,64 @@
 #![allow(clippy::not_unsafe_ptr_arg_deref)]
 #![allow(clippy::missing_safety_doc)]
 
+#[cfg(feature = "futures")]
+extern crate futures_core;
 extern crate libsoundio_sys as raw;
 
 mod backend;
+mod blocking;
+mod buffer;
 mod channels;
+mod config;
 mod context;
+mod convert;
 mod device;
+mod dither;
 mod error;
+#[cfg(feature = "futures")]
+mod event_stream;
+mod events;
 mod format;
+mod handle;
 mod instream;
 mod layout;
 mod outstream;
+mod queue;
+mod ring_buffer;
 mod sample;
+mod scheduler;
+mod timestamp;
+mod typed;
 mod types;
 mod util;
+mod volume;
+mod watcher;
 
 pub use self::backend::*;
+pub use self::blocking::*;
+pub use self::buffer::*;
 pub use self::channels::*;
+pub use self::config::*;
 pub use self::context::*;
+pub use self::convert::*;
 pub use self::device::*;
+pub use self::dither::*;
 pub use self::error::*;
+#[cfg(feature = "futures")]
+pub use self::event_stream::*;
+pub use self::events::*;
 pub use self::format::*;
+pub use self::handle::*;
 pub use self::instream::*;
 pub use self::layout::*;
 pub use self::outstream::*;
+pub use self::ring_buffer::*;
 pub use self::sample::*;
+pub use self::scheduler::*;
+pub use self::timestamp::*;
+pub use self::typed::*;
 pub use self::types::*;
+pub use self::watcher::*;
 
 use self::util::*;
 
@@ -1,14 +1,30 @@
 extern crate libsoundio_sys as raw;
 
+use super::blocking::*;
+use super::buffer::*;
+use super::config::*;
+use super::context::*;
+use super::device::*;
+use super::dither::*;
 use super::error::*;
+use super::events::*;
 use super::format::*;
+use super::handle::*;
+use super::layout::*;
+use super::ring_buffer::*;
 use super::sample::*;
+use super::timestamp::*;
+use super::typed::*;
 use super::util::*;
+use super::volume::*;
 
+use std::ffi::CString;
 use std::marker::PhantomData;
 use std::os::raw::{c_double, c_int};
 use std::ptr;
+use std::result;
 use std::slice;
+use std::sync::{Arc, Mutex};
 
 /// This is called when an outstream needs to be written to. The `OutStreamUserData` struct is obtained
 /// from the stream.userdata, then the user-supplied callback is called with an `OutStreamWriter`
@@ -24,6 +40,10 @@ pub extern "C" fn outstream_write_callback(
 
     let mut stream_writer = OutStreamWriter {
         outstream: userdata.outstream,
+        event_sink: &userdata.event_sink,
+        control: &userdata.control,
+        volume: &userdata.volume,
+        gain: &mut userdata.gain,
         frame_count_min: frame_count_min as _,
         frame_count_max: frame_count_max as _,
         write_started: false,
@@ -33,6 +53,15 @@ pub extern "C" fn outstream_write_callback(
     };
 
     (userdata.write_callback)(&mut stream_writer);
+    drop(stream_writer);
+
+    // Handles can't measure the latency themselves since it is only allowed in the callback.
+    if userdata.control.latency_wanted() {
+        let mut latency: c_double = 0.0;
+        if unsafe { raw::soundio_outstream_get_latency(stream, &mut latency) } == 0 {
+            userdata.control.set_latency(latency);
+        }
+    }
 }
 
 pub extern "C" fn outstream_underflow_callback(stream: *mut raw::SoundIoOutStream) {
@@ -40,10 +69,9 @@ pub extern "C" fn outstream_underflow_callback(stream: *mut raw::SoundIoOutStrea
     let raw_userdata_pointer = unsafe { (*stream).userdata as *mut OutStreamUserData };
     let userdata = unsafe { &mut (*raw_userdata_pointer) };
 
+    userdata.event_sink.record(Event::Underflow);
     if let Some(ref mut cb) = userdata.underflow_callback {
         cb();
-    } else {
-        println!("Underflow!");
     }
 }
 
@@ -52,17 +80,17 @@ pub extern "C" fn outstream_error_callback(stream: *mut raw::SoundIoOutStream, e
     let raw_userdata_pointer = unsafe { (*stream).userdata as *mut OutStreamUserData };
     let userdata = unsafe { &mut (*raw_userdata_pointer) };
 
+    let err = Error::from(err);
+    userdata.event_sink.record(Event::StreamError(err));
     if let Some(ref mut cb) = userdata.error_callback {
-        cb(err.into());
-    } else {
-        println!("Error: {}", Error::from(err));
+        cb(err);
     }
 }
 
 /// OutStream represents an output stream for playback.
 ///
-/// It is obtained from `Device` using `Device::open_outstream()` and
-/// can be started and paused.
+/// It is obtained from `Device` using `Device::outstream_builder()` or `Device::open_outstream()`
+/// and can be started and paused.
 pub struct OutStream<'a> {
     pub userdata: Box<OutStreamUserData<'a>>,
 
@@ -78,11 +106,29 @@ pub struct OutStreamUserData<'a> {
     pub write_callback: Box<dyn FnMut(&mut OutStreamWriter) + 'a>,
     pub underflow_callback: Option<Box<dyn FnMut() + 'a>>,
     pub error_callback: Option<Box<dyn FnMut(Error) + 'a>>,
+
+    // Callback events are recorded here.
+    pub event_sink: Arc<EventSink>,
+
+    // Shared with the stream's handles.
+    pub(crate) control: Arc<StreamControl<raw::SoundIoOutStream>>,
+    // Held while the stream is created and destroyed, since that refs and unrefs the
+    // device. See `Context::start_event_thread()`.
+    pub(crate) device_lock: Arc<Mutex<()>>,
+    pub(crate) volume: Arc<VolumeControl>,
+    // The software volume's gain, which is only used in the write callback.
+    pub(crate) gain: GainRamp,
+
+    // libsoundio keeps a pointer to the stream name, so we own the string here.
+    pub name: Option<CString>,
 }
 
 impl<'a> Drop for OutStreamUserData<'a> {
     fn drop(&mut self) {
         unsafe {
+            // Handles must not use the stream once it is destroyed.
+            self.control.close();
+            let _lock = lock_devices(&self.device_lock);
             raw::soundio_outstream_destroy(self.outstream);
         }
     }
@@ -134,9 +180,6 @@ impl<'a> OutStream<'a> {
         }
     }
 
-    // TODO: pause() can be called from the write callback, so add it to
-    // OutStreamWriter.
-
     /// If the underlying backend and device support pausing, this pauses the
     /// stream. The `write_callback()` may be called a few more times if
     /// the buffer is not full.
@@ -158,6 +201,7 @@ impl<'a> OutStream<'a> {
     ///    pausing/unpausing.
     /// * `Error::Invalid` - outstream not opened and started
     ///
+    /// To pause from inside the write callback use `OutStreamWriter::pause()`.
     pub fn pause(&mut self, pause: bool) -> Result<()> {
         match unsafe { raw::soundio_outstream_pause(self.userdata.outstream, pause as i8) } {
             0 => Ok(()),
@@ -214,7 +258,7 @@ impl<'a> OutStream<'a> {
     /// WASAPI uses this for the session display name.
     /// Must not contain a colon (":").
     ///
-    /// TODO: Currently there is no way to set this.
+    /// It can be set when opening the stream using `OutStreamBuilder::name()`.
     pub fn name(&self) -> String {
         unsafe { utf8_to_string((*self.userdata.outstream).name) }
     }
@@ -229,15 +273,466 @@ impl<'a> OutStream<'a> {
     pub fn bytes_per_sample(&self) -> i32 {
         unsafe { (*self.userdata.outstream).bytes_per_sample as _ }
     }
+
+    /// The sink that events from this stream's callbacks are recorded in.
+    pub fn event_sink(&self) -> &Arc<EventSink> {
+        &self.userdata.event_sink
+    }
+
+    /// Get a handle that can control the stream from other threads. See `OutStreamHandle`.
+    pub fn handle(&self) -> OutStreamHandle {
+        OutStreamHandle::new(self.userdata.control.clone(), self.userdata.volume.clone())
+    }
+
+    /// Set the volume of the stream, from 0.0 (silent) to 1.0 (full volume).
+    ///
+    /// If the backend can set the stream volume (e.g. PulseAudio) it is used. Otherwise,
+    /// or if the stream was opened with `OutStreamBuilder::software_volume()`, the samples
+    /// are scaled when each write ends. This is the software volume. Changes of the
+    /// software volume are ramped over a few milliseconds so that they don't click.
+    ///
+    /// # Errors
+    ///
+    /// * `Error::Invalid` - `volume` is not between 0.0 and 1.0.
+    /// * `Error::BackendDisconnected`
+    /// * `Error::Streaming`
+    pub fn set_volume(&mut self, volume: f32) -> Result<()> {
+        let outstream = self.userdata.outstream;
+        self.userdata.volume.set(volume, |v| {
+            match unsafe { raw::soundio_outstream_set_volume(outstream, v) } {
+                0 => Ok(()),
+                e => Err(e.into()),
+            }
+        })
+    }
+
+    /// The volume of the stream that was last set with `set_volume()`,
+    /// `OutStreamBuilder::volume()` or an `OutStreamHandle`. It defaults to 1.0.
+    pub fn volume(&self) -> f32 {
+        self.userdata.volume.volume()
+    }
+
+    /// Returns true if the volume is applied by scaling the samples in the write path
+    /// because the backend can't set it. See `set_volume()`.
+    pub fn is_software_volume(&self) -> bool {
+        self.userdata.volume.is_software()
+    }
+}
+
+type WriteCallback<'a> = Box<dyn FnMut(&mut OutStreamWriter) + 'a>;
+
+/// `OutStreamBuilder` is used to configure and open an `OutStream`. It is obtained
+/// from `Device::outstream_builder()`.
+///
+/// Every parameter is optional except the write callback. Parameters that are not
+/// set default to the device's current settings (`Device::current_sample_rate()`,
+/// `Device::current_format()` and `Device::current_layout()`) if they are known,
+/// and otherwise to libsoundio's defaults.
+///
+/// # Examples
+///
+/// ```
+/// # fn foo() -> Result<(), soundio::Error> {
+/// let mut ctx = soundio::Context::new();
+/// ctx.connect_backend(soundio::Backend::Dummy)?;
+/// let dev = ctx.default_output_device()?;
+///
+/// let mut stream = dev
+///     .outstream_builder()
+///     .sample_rate(44100)
+///     .format(soundio::Format::Float32LE)
+///     .software_latency(0.1)
+///     .name("Synth")
+///     .write_callback(|stream: &mut soundio::OutStreamWriter| {
+///         let frame_count_max = stream.frame_count_max();
+///         stream.begin_write(frame_count_max).unwrap();
+///     })
+///     .underflow_callback(|| println!("Underflow"))
+///     .open()?;
+///
+/// stream.start()?;
+/// # Ok(())
+/// # }
+/// ```
+pub struct OutStreamBuilder<'a, 'b> {
+    device: &'a Device<'a>,
+
+    sample_rate: Option<i32>,
+    format: Option<Format>,
+    layout: Option<ChannelLayout>,
+    software_latency: Option<f64>,
+    name: Option<String>,
+    non_terminal_hint: bool,
+    volume: Option<f32>,
+    software_volume: bool,
+
+    write_callback: Option<WriteCallback<'b>>,
+    underflow_callback: Option<Box<dyn FnMut() + 'b>>,
+    error_callback: Option<Box<dyn FnMut(Error) + 'b>>,
+    event_sink: Option<Arc<EventSink>>,
+}
+
+impl<'a, 'b: 'a> OutStreamBuilder<'a, 'b> {
+    /// Create a builder for the given device. Prefer `Device::outstream_builder()`.
+    pub fn new(device: &'a Device<'a>) -> OutStreamBuilder<'a, 'b> {
+        let sample_rate = device.current_sample_rate();
+        let format = device.current_format();
+        let layout = device.current_layout();
+
+        OutStreamBuilder {
+            device,
+            sample_rate: if sample_rate > 0 {
+                Some(sample_rate)
+            } else {
+                None
+            },
+            format: if format != Format::Invalid {
+                Some(format)
+            } else {
+                None
+            },
+            layout: if !layout.channels.is_empty() {
+                Some(layout)
+            } else {
+                None
+            },
+            software_latency: None,
+            name: None,
+            non_terminal_hint: false,
+            volume: None,
+            software_volume: false,
+            write_callback: None,
+            underflow_callback: None,
+            error_callback: None,
+            event_sink: None,
+        }
+    }
+
+    /// The requested sample rate. Check supported sample rates first with `Device::sample_rates()`.
+    pub fn sample_rate(mut self, sample_rate: i32) -> Self {
+        self.sample_rate = Some(sample_rate);
+        self
+    }
+
+    /// The requested format. Check supported formats first with `Device::formats()`.
+    pub fn format(mut self, format: Format) -> Self {
+        self.format = Some(format);
+        self
+    }
+
+    /// The requested channel layout. Check supported layouts first with `Device::layouts()`.
+    pub fn layout(mut self, layout: ChannelLayout) -> Self {
+        self.layout = Some(layout);
+        self
+    }
+
+    /// Set the sample rate, format and layout at once, for example from
+    /// `Device::negotiate_config()`.
+    pub fn config(self, config: StreamConfig) -> Self {
+        self.sample_rate(config.sample_rate)
+            .format(config.format)
+            .layout(config.layout)
+    }
+
+    /// The requested software latency in seconds. With a lower value your write callback
+    /// will be called more often and work in smaller blocks but latency will be lower.
+    ///
+    /// See `OutStream::software_latency()` for more details. If this is not set
+    /// the backend chooses a default.
+    pub fn software_latency(mut self, latency: f64) -> Self {
+        self.software_latency = Some(latency);
+        self
+    }
+
+    /// The name of the stream. PulseAudio uses this for the stream name,
+    /// JACK uses it for the client name and WASAPI uses it for the session
+    /// display name. Any colons are removed. The default is "SoundIoOutStream".
+    pub fn name(mut self, name: &str) -> Self {
+        self.name = Some(name.chars().filter(|&x| x != ':' && x != '\0').collect());
+        self
+    }
+
+    /// Hint that this output stream is nonterminal. This is used by JACK and it
+    /// means that the output stream data originates from an input stream.
+    /// Defaults to `false`.
+    pub fn non_terminal_hint(mut self, non_terminal_hint: bool) -> Self {
+        self.non_terminal_hint = non_terminal_hint;
+        self
+    }
+
+    /// The initial volume of the stream, from 0.0 to 1.0. It can be changed later with
+    /// `OutStream::set_volume()`. Defaults to 1.0.
+    pub fn volume(mut self, volume: f32) -> Self {
+        self.volume = Some(volume);
+        self
+    }
+
+    /// Always apply the volume by scaling the samples in the write path, even if the
+    /// backend could set it. See `OutStream::set_volume()`. Defaults to `false`.
+    pub fn software_volume(mut self, software_volume: bool) -> Self {
+        self.software_volume = software_volume;
+        self
+    }
+
+    /// Required callback that is called to allow you to write audio data to the outstream.
+    /// See `OutStreamWriter` for more details.
+    pub fn write_callback<WriteCB>(mut self, write_callback: WriteCB) -> Self
+    where
+        WriteCB: 'b + FnMut(&mut OutStreamWriter),
+    {
+        self.write_callback = Some(Box::new(write_callback));
+        self
+    }
+
+    /// Optional callback that is called when your write callback is too slow and the output skips.
+    pub fn underflow_callback<UnderflowCB>(mut self, underflow_callback: UnderflowCB) -> Self
+    where
+        UnderflowCB: 'b + FnMut(),
+    {
+        self.underflow_callback = Some(Box::new(underflow_callback));
+        self
+    }
+
+    /// Optional error callback. The error is always `Error::Streaming`, which
+    /// means the stream is in an invalid state and must be destroyed.
+    pub fn error_callback<ErrorCB>(mut self, error_callback: ErrorCB) -> Self
+    where
+        ErrorCB: 'b + FnMut(Error),
+    {
+        self.error_callback = Some(Box::new(error_callback));
+        self
+    }
+
+    /// The sink that underflows, errors and failures to end a write are recorded in.
+    /// By default each stream has its own sink that only counts events. See `EventSink`.
+    pub fn event_sink(mut self, event_sink: Arc<EventSink>) -> Self {
+        self.event_sink = Some(event_sink);
+        self
+    }
+
+    /// Open the output stream. After opening you can start, pause and stop it
+    /// using the functions on the `OutStream` that is returned. Then your write callback
+    /// will be called.
+    ///
+    /// # Errors
+    ///
+    /// * `Error::UnsupportedSampleRate` - the device does not support the sample rate.
+    /// * `Error::UnsupportedFormat` - the device does not support the format.
+    /// * `Error::UnsupportedLayout` - the device does not support the channel layout.
+    /// * `Error::Invalid`
+    ///   - no write callback was set
+    ///   - the volume is not between 0.0 and 1.0
+    ///   - `Device::aim()` is not `DeviceAim::Output`
+    ///   - `format` is not valid
+    ///   - `channel_count` is greater than `SOUNDIO_MAX_CHANNELS` (24).
+    /// * `Error::NoMem`
+    /// * `Error::OpeningDevice`
+    /// * `Error::BackendDisconnected`
+    /// * `Error::SystemResources`
+    /// * `Error::NoSuchClient` - when JACK returns `JackNoSuchClient`
+    /// * `Error::IncompatibleBackend` - `OutStream::channel_count()` is greater than the number of channels the backend can handle.
+    /// * `Error::IncompatibleDevice` - stream parameters requested are not compatible with the chosen device.
+    pub fn open(self) -> Result<OutStream<'b>> {
+        let write_callback = match self.write_callback {
+            Some(cb) => cb,
+            None => return Err(Error::Invalid),
+        };
+        let volume = self.volume.unwrap_or(1.0);
+        check_volume(volume)?;
+
+        if let Some(sample_rate) = self.sample_rate {
+            if !self.device.supports_sample_rate(sample_rate) {
+                return Err(Error::UnsupportedSampleRate);
+            }
+        }
+        if let Some(format) = self.format {
+            if !self.device.supports_format(format) {
+                return Err(Error::UnsupportedFormat);
+            }
+        }
+        if let Some(ref layout) = self.layout {
+            if !self.device.supports_layout(layout.clone()) {
+                return Err(Error::UnsupportedLayout);
+            }
+        }
+
+        let outstream = {
+            let _lock = lock_devices(&self.device.device_lock);
+            unsafe { raw::soundio_outstream_create(self.device.device) }
+        };
+        if outstream.is_null() {
+            // Note that we should really abort() here (that's what the rest of Rust
+            // does on OOM), but there is no stable way to abort in Rust that I can see.
+            panic!("soundio_outstream_create() failed (out of memory).");
+        }
+
+        let name = self
+            .name
+            .map(|n| CString::new(n).expect("Stream name contains a null byte"));
+
+        unsafe {
+            // Anything that isn't set is left at libsoundio's default.
+            if let Some(sample_rate) = self.sample_rate {
+                (*outstream).sample_rate = sample_rate;
+            }
+            if let Some(format) = self.format {
+                (*outstream).format = format.into();
+            }
+            if let Some(layout) = self.layout {
+                (*outstream).layout = layout.into();
+            }
+            if let Some(latency) = self.software_latency {
+                (*outstream).software_latency = latency;
+            }
+            if let Some(ref name) = name {
+                (*outstream).name = name.as_ptr();
+            }
+            if self.volume.is_some() && !self.software_volume {
+                (*outstream).volume = volume;
+            }
+            (*outstream).non_terminal_hint = self.non_terminal_hint as i8;
+            (*outstream).write_callback = outstream_write_callback;
+            (*outstream).underflow_callback = Some(outstream_underflow_callback);
+            (*outstream).error_callback = Some(outstream_error_callback);
+        }
+
+        let mut stream = OutStream {
+            userdata: Box::new(OutStreamUserData {
+                outstream,
+                write_callback,
+                underflow_callback: self.underflow_callback,
+                error_callback: self.error_callback,
+                event_sink: self
+                    .event_sink
+                    .unwrap_or_else(|| Arc::new(EventSink::new())),
+                control: Arc::new(StreamControl::new(outstream)),
+                device_lock: self.device.device_lock.clone(),
+                volume: Arc::new(VolumeControl::new(volume, self.software_volume)),
+                gain: GainRamp::new(volume),
+                name,
+            }),
+            phantom: PhantomData,
+        };
+
+        // Safe userdata pointer.
+        unsafe {
+            (*stream.userdata.outstream).userdata =
+                stream.userdata.as_mut() as *mut OutStreamUserData as *mut _;
+        }
+
+        match unsafe { raw::soundio_outstream_open(stream.userdata.outstream) } {
+            0 => {}
+            x => return Err(x.into()),
+        };
+
+        match unsafe { (*stream.userdata.outstream).layout_error } {
+            0 => {}
+            x => return Err(x.into()),
+        }
+
+        // The backends that use the volume when opening the stream are the ones that
+        // support setting it, so this finds out whether the software volume is needed.
+        if self.volume.is_some() {
+            stream.set_volume(volume)?;
+        }
+
+        Ok(stream)
+    }
+
+    /// Open the output stream as a `TypedOutStream`, whose format is `T::FORMAT`.
+    /// `write_callback` is called with a `TypedOutStreamWriter` instead of an `OutStreamWriter`,
+    /// so it gets `T` samples directly. Any write callback that was set is ignored.
+    ///
+    /// # Errors
+    ///
+    /// * `Error::UnsupportedFormat` - the device doesn't support `T::FORMAT`.
+    /// * The errors returned by `open()`.
+    pub fn open_typed<T, F>(self, mut write_callback: F) -> Result<TypedOutStream<'b, T>>
+    where
+        T: NativeSample,
+        F: 'b + FnMut(&mut TypedOutStreamWriter<T>),
+    {
+        let stream = self
+            .format(T::FORMAT)
+            .write_callback(move |stream: &mut OutStreamWriter| {
+                write_callback(&mut TypedOutStreamWriter::new(stream))
+            })
+            .open()?;
+        Ok(TypedOutStream::new(stream))
+    }
+
+    /// Open the output stream as a `BlockingOutStream`, which you write to instead of
+    /// supplying a write callback. Any write callback that was set is ignored, and the
+    /// format is set to `T::FORMAT`. The underflow and error callbacks are still called.
+    ///
+    /// `buffer_duration` is the amount of audio in seconds that can be queued by
+    /// `BlockingOutStream::write()` before it blocks. The stream is started immediately.
+    ///
+    /// # Errors
+    ///
+    /// The same as `open()` and `OutStream::start()`, and `Error::NoMem` if the
+    /// ring buffer could not be created.
+    pub fn open_blocking<T: NativeSample + 'static>(
+        mut self,
+        buffer_duration: f64,
+    ) -> Result<BlockingOutStream<'b, T>> {
+        let device = self.device;
+        let shared = Arc::new(BlockingShared::new());
+        let (handoff, mut consumer) = handoff();
+
+        let cb_shared = shared.clone();
+        let mut underflow_callback = self.underflow_callback.take();
+        self.underflow_callback = Some(Box::new(move || {
+            cb_shared.xrun();
+            if let Some(ref mut cb) = underflow_callback {
+                cb();
+            }
+        }));
+
+        let cb_shared = shared.clone();
+        let mut error_callback = self.error_callback.take();
+        self.error_callback = Some(Box::new(move |err| {
+            cb_shared.set_errored();
+            if let Some(ref mut cb) = error_callback {
+                cb(err);
+            }
+        }));
+
+        let cb_shared = shared.clone();
+        let stream = self
+            .format(T::FORMAT)
+            .write_callback(move |stream: &mut OutStreamWriter| {
+                if let Some(consumer) = consumer.get() {
+                    blocking_write_callback::<T>(stream, consumer, &cb_shared)
+                }
+            })
+            .open()?;
+
+        // The stream might not have the sample rate and layout that were asked for, so
+        // the buffer is sized once it is open.
+        let channel_count = unsafe { (*stream.userdata.outstream).layout.channel_count as usize };
+        let ring_buffer = RingBuffer::for_device(
+            device,
+            ring_buffer_size::<T>(buffer_duration, stream.sample_rate(), channel_count),
+        )?;
+        let (producer, consumer) = ring_buffer.split();
+        handoff.send(consumer);
+
+        BlockingOutStream::new(stream, producer, shared)
+    }
 }
 
 /// `OutStreamWriter` is passed to the write callback and can be used to write to the stream.
 ///
 /// You start by calling `begin_write()` then you can write the samples. When the `OutStreamWriter``
-/// is dropped the write is committed. An error at that point is written to the console and ignored.
+/// is dropped the write is committed. An error at that point is recorded in the stream's `EventSink`.
 ///
 pub struct OutStreamWriter<'a> {
     outstream: *mut raw::SoundIoOutStream,
+    event_sink: &'a EventSink,
+    control: &'a StreamControl<raw::SoundIoOutStream>,
+    volume: &'a VolumeControl,
+    gain: &'a mut GainRamp,
     frame_count_min: usize,
     frame_count_max: usize,
 
@@ -253,10 +748,13 @@ pub struct OutStreamWriter<'a> {
 }
 
 impl<'a> OutStreamWriter<'a> {
-    /// Start a write. You can only call this once per callback otherwise it panics.
+    /// Start a write. If the returned frame count is less than you wanted you can
+    /// call `end_write()` and then `begin_write()` again to write the rest.
     ///
     /// frame_count is the number of frames you want to write. It must be between
-    /// frame_count_min and frame_count_max or `begin_write()` will panic.
+    /// frame_count_min and frame_count_max or `begin_write()` will panic. After each
+    /// write `frame_count_min()` and `frame_count_max()` are reduced by the number of
+    /// frames written.
     ///
     /// It returns the number of frames you must actually write. The returned value
     /// will always be less than or equal to the provided value.
@@ -295,6 +793,8 @@ impl<'a> OutStreamWriter<'a> {
             0 => {
                 self.write_started = true;
                 self.frame_count = actual_frame_count as _;
+                self.frame_count_min = self.frame_count_min.saturating_sub(self.frame_count);
+                self.frame_count_max -= self.frame_count;
                 // Return now if there's no frames to actually read.
                 if actual_frame_count <= 0 {
                     return Ok(0);
@@ -318,9 +818,14 @@ impl<'a> OutStreamWriter<'a> {
         }
     }
 
-    /// Commits the write that you began with `begin_write()`.
+    /// Commits the write that you began with `begin_write()`. This does nothing if
+    /// there is no write in progress.
+    ///
+    /// If you don't call this it is called when the `OutStreamWriter` is dropped, and
+    /// any error is recorded in the stream's `EventSink` instead of being returned.
     ///
-    /// Errors are currently are just printed to the console and ignored.
+    /// If the software volume is in use (see `OutStream::set_volume()`) the samples are
+    /// scaled here, so don't read them back after writing.
     ///
     /// # Errors
     ///
@@ -329,22 +834,38 @@ impl<'a> OutStreamWriter<'a> {
     ///   also get an `underflow_callback()`, and you might not get
     ///   this error code when an underflow occurs. Unlike `Error::Streaming`,
     ///   the outstream is still in a valid state and streaming can continue.
-    pub fn end_write(&mut self) {
-        if self.write_started {
+    pub fn end_write(&mut self) -> Result<()> {
+        if !self.write_started {
+            return Ok(());
+        }
+        // The write is over even if it failed, so don't end it again in `drop()`.
+        self.write_started = false;
+        if self.volume.is_software() && self.frame_count > 0 {
             unsafe {
-                match raw::soundio_outstream_end_write(self.outstream) {
-                    0 => {
-                        self.write_started = false;
-                    }
-                    x => println!("Error writing outstream: {}", Error::from(x)),
-                }
+                self.gain.apply(
+                    self.volume.volume(),
+                    &self.channel_areas,
+                    self.format(),
+                    self.sample_rate(),
+                    self.frame_count,
+                );
             }
         }
+        match unsafe { raw::soundio_outstream_end_write(self.outstream) } {
+            0 => {
+                self.control.advance(self.frame_count);
+                Ok(())
+            }
+            e => Err(e.into()),
+        }
     }
 
     /// Get the minimum frame count that you can call `begin_write()` with.
     /// Retreive this value before calling `begin_write()` to ensure you read the correct number
     /// of frames.
+    ///
+    /// This is reduced by the number of frames written after each `begin_write()`, so it
+    /// is what is left for the rest of the callback rather than the value it started with.
     pub fn frame_count_min(&self) -> usize {
         self.frame_count_min
     }
@@ -352,10 +873,56 @@ impl<'a> OutStreamWriter<'a> {
     /// Get the maximum frame count that you can call `begin_write()` with.
     /// Retreive this value before calling `begin_write()` to ensure you read the correct number
     /// of frames.
+    ///
+    /// This is reduced by the number of frames written after each `begin_write()`, so it
+    /// is what is left for the rest of the callback rather than the value it started with.
     pub fn frame_count_max(&self) -> usize {
         self.frame_count_max
     }
 
+    /// Returns true if `request_stop()` has been called here or on one of the stream's
+    /// handles. See `OutStreamHandle`.
+    pub fn stop_requested(&self) -> bool {
+        self.control.stop_requested()
+    }
+
+    /// Set the "stop requested" flag, for example to tell the thread that owns the stream
+    /// that the end of the audio has been reached. It can be checked with
+    /// `OutStreamHandle::stop_requested()`.
+    pub fn request_stop(&self) {
+        self.control.request_stop()
+    }
+
+    /// The channel areas of the current write, which are empty until `begin_write()`.
+    pub(crate) fn channel_areas(&self) -> &[raw::SoundIoChannelArea] {
+        &self.channel_areas
+    }
+
+    /// The position of the current write in the stream: the number of frames that were
+    /// written before it since the stream was opened. If there is no write in progress
+    /// it is the position of the next one. Frames are counted when a write ends
+    /// successfully, and frames removed with `clear_buffer()` are still counted.
+    pub fn frame_position(&self) -> u64 {
+        self.control.frame_position()
+    }
+
+    /// The position of the current write in the stream together with the estimated time
+    /// at which its first frame will be heard, which is now plus `get_latency()`. With
+    /// `StreamTimestamp::time_of()` and `StreamTimestamp::frame_at()` you can convert
+    /// between frames and times, for example to start a sound at an exact time.
+    ///
+    /// # Errors
+    ///
+    /// * `Error::Streaming`
+    pub fn timestamp(&mut self) -> Result<StreamTimestamp> {
+        let latency = self.get_latency()?;
+        Ok(timestamp_from_now(
+            self.frame_position(),
+            latency,
+            self.sample_rate(),
+        ))
+    }
+
     /// Get the actual frame count that you did call `begin_write()` with. Panics if you haven't called
     /// `begin_write()` yet.
     pub fn frame_count(&self) -> usize {
@@ -378,6 +945,11 @@ impl<'a> OutStreamWriter<'a> {
         unsafe { (*self.outstream).sample_rate as _ }
     }
 
+    /// Get the format of the stream.
+    pub fn format(&self) -> Format {
+        unsafe { (*self.outstream).format.into() }
+    }
+
     /// Obtain the total number of seconds that the next frame written after the
     /// last frame written from the write callback will take to become
     /// audible. This includes both software and hardware latency. In other words,
@@ -398,6 +970,40 @@ impl<'a> OutStreamWriter<'a> {
         }
     }
 
+    /// Pause or unpause the stream from inside the write callback, for example to stop
+    /// exactly at the end of a file. See `OutStream::pause()` for details.
+    ///
+    /// Any write in progress is still committed when it ends. After pausing, the write
+    /// callback may be called a few more times if the buffer is not full.
+    ///
+    /// # Errors
+    ///
+    /// * `Error::BackendDisconnected`
+    /// * `Error::Streaming`
+    /// * `Error::IncompatibleDevice` - device does not support pausing/unpausing.
+    /// * `Error::IncompatibleBackend` - backend does not support pausing/unpausing.
+    pub fn pause(&mut self, pause: bool) -> Result<()> {
+        match unsafe { raw::soundio_outstream_pause(self.outstream, pause as i8) } {
+            0 => Ok(()),
+            e => Err(e.into()),
+        }
+    }
+
+    /// Clear the output stream buffer from inside the write callback. See
+    /// `OutStream::clear_buffer()` for details.
+    ///
+    /// # Errors
+    ///
+    /// * `Error::Streaming`
+    /// * `Error::IncompatibleBackend`
+    /// * `Error::IncompatibleDevice`
+    pub fn clear_buffer(&mut self) -> Result<()> {
+        match unsafe { raw::soundio_outstream_clear_buffer(self.outstream) } {
+            0 => Ok(()),
+            e => Err(e.into()),
+        }
+    }
+
     /// Set the value of a sample/channel. This panics if the `channel` or `frame` are
     /// out of range or if you haven't called `begin_write()` yet.
     ///
@@ -460,25 +1066,286 @@ impl<'a> OutStreamWriter<'a> {
         }
     }
 
-    // TODO: To acheive speed *and* safety I can use iterators. That will be in a future API.
+    /// Set the value of a sample/channel like `set_sample()`, but if the stream has an
+    /// integer format, dither is added using `ditherer` (see `Ditherer`). For float
+    /// formats this is the same as `set_sample()`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// fn write_callback(stream: &mut soundio::OutStreamWriter, ditherer: &mut soundio::Ditherer) {
+    ///     let frame_count_max = stream.frame_count_max();
+    ///     stream.begin_write(frame_count_max).unwrap();
+    ///     for f in 0..stream.frame_count() {
+    ///         for c in 0..stream.channel_count() {
+    ///             stream.set_sample_dithered(ditherer, c, f, 0.001f32);
+    ///         }
+    ///     }
+    /// }
+    /// ```
+    pub fn set_sample_dithered<T: Sample>(
+        &mut self,
+        ditherer: &mut Ditherer,
+        channel: usize,
+        frame: usize,
+        sample: T,
+    ) {
+        let format = self.format();
+        let x = ditherer.dither(channel, T::to_f64(sample), format);
+        self.set_sample::<f64>(channel, frame, x);
+    }
+
+    /// Get the samples of one channel as a mutable slice of `frame_count()` samples.
+    /// This is much faster than calling `set_sample()` for every sample, but it is
+    /// only possible if `T` is the stream format (see `NativeSample`) and the
+    /// samples of the channel are next to each other in memory. Otherwise the
+    /// reason is returned and you can fall back to `set_sample()`.
+    ///
+    /// Panics if `channel` is out of range.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// fn write_callback(stream: &mut soundio::OutStreamWriter) {
+    ///     let frame_count_max = stream.frame_count_max();
+    ///     stream.begin_write(frame_count_max).unwrap();
+    ///     for c in 0..stream.channel_count() {
+    ///         match stream.channel_slice_mut::<f32>(c) {
+    ///             Ok(samples) => {
+    ///                 for s in samples {
+    ///                     *s = 0.0;
+    ///                 }
+    ///             }
+    ///             Err(_) => {
+    ///                 for f in 0..stream.frame_count() {
+    ///                     stream.set_sample::<f32>(c, f, 0.0);
+    ///                 }
+    ///             }
+    ///         }
+    ///     }
+    /// }
+    /// ```
+    pub fn channel_slice_mut<T: NativeSample>(
+        &mut self,
+        channel: usize,
+    ) -> result::Result<&mut [T], SliceError> {
+        if !self.write_started {
+            return Err(SliceError::NotStarted);
+        }
+        assert!(channel < self.channel_count(), "Channel out of range");
+        if self.frame_count == 0 {
+            return Ok(&mut []);
+        }
+
+        let ptr = channel_ptr::<T>(&self.channel_areas, self.format(), channel)?;
+        unsafe { Ok(slice::from_raw_parts_mut(ptr, self.frame_count)) }
+    }
+
+    /// Get the samples of all channels as a single interleaved slice of
+    /// `frame_count() * channel_count()` samples, i.e. LRLRLR for stereo.
+    ///
+    /// This is only possible if `T` is the stream format (see `NativeSample`) and
+    /// the backend uses an interleaved buffer. Otherwise the reason is returned and you
+    /// can fall back to `channel_slice_mut()` or `set_sample()`.
+    pub fn interleaved_slice_mut<T: NativeSample>(
+        &mut self,
+    ) -> result::Result<&mut [T], SliceError> {
+        if !self.write_started {
+            return Err(SliceError::NotStarted);
+        }
+        if self.frame_count == 0 {
+            return Ok(&mut []);
+        }
+
+        let ptr = interleaved_ptr::<T>(&self.channel_areas, self.format())?;
+        unsafe {
+            Ok(slice::from_raw_parts_mut(
+                ptr,
+                self.frame_count * self.channel_areas.len(),
+            ))
+        }
+    }
+
+    /// Iterate over the frames of the write. Each frame lets you set the sample of
+    /// every channel, converted from `T` to the stream format. The conversion function
+    /// is chosen once when this is called, so it is faster than `set_sample()`, though
+    /// not as fast as `channel_slice_mut()`. Panics if you haven't called `begin_write()` yet.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// fn write_callback(stream: &mut soundio::OutStreamWriter) {
+    ///     let frame_count_max = stream.frame_count_max();
+    ///     stream.begin_write(frame_count_max).unwrap();
+    ///     for mut frame in stream.frames_mut::<f32>() {
+    ///         for c in 0..frame.channel_count() {
+    ///             frame.set(c, 0.0);
+    ///         }
+    ///     }
+    /// }
+    /// ```
+    pub fn frames_mut<T: Sample>(&mut self) -> FramesMut<'_, T> {
+        assert!(self.write_started);
+        unsafe { FramesMut::new(&self.channel_areas, self.format(), self.frame_count) }
+    }
+
+    /// Iterate over the samples of one channel, converting from `T` to the stream format.
+    /// Panics if `channel` is out of range or if you haven't called `begin_write()` yet.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// fn write_callback(stream: &mut soundio::OutStreamWriter) {
+    ///     let frame_count_max = stream.frame_count_max();
+    ///     stream.begin_write(frame_count_max).unwrap();
+    ///     for c in 0..stream.channel_count() {
+    ///         for (f, sample) in stream.channel_samples_mut::<f64>(c).enumerate() {
+    ///             sample.set((f as f64 * 0.1).sin());
+    ///         }
+    ///     }
+    /// }
+    /// ```
+    pub fn channel_samples_mut<T: Sample>(&mut self, channel: usize) -> ChannelSamplesMut<'_, T> {
+        assert!(self.write_started);
+        assert!(channel < self.channel_count(), "Channel out of range");
+        if self.frame_count == 0 {
+            return unsafe { ChannelSamplesMut::new(&NO_AREA, self.format(), 0) };
+        }
+        unsafe {
+            ChannelSamplesMut::new(
+                &self.channel_areas[channel],
+                self.format(),
+                self.frame_count,
+            )
+        }
+    }
+
+    /// Write `frame_count` frames, calling `chunk_fn` for each contiguous chunk of the
+    /// output buffer. This does the `begin_write()` and `end_write()` loop for you: the
+    /// backend might not be able to give you all of the frames at once, in which case
+    /// `chunk_fn` is called several times. It is passed a `WriteChunk` that lets you
+    /// write the samples as `T`, and the offset of the chunk's first frame from the start
+    /// of the write.
+    ///
+    /// `frame_count` must be between `frame_count_min()` and `frame_count_max()` or this
+    /// panics. Returns the total number of frames written, which may be less than
+    /// `frame_count` if the backend returned no frames.
+    ///
+    /// # Errors
+    ///
+    /// The same as `begin_write()` and `end_write()`. Frames written before the error are
+    /// still committed.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// fn write_callback(stream: &mut soundio::OutStreamWriter, phase: &mut f32) {
+    ///     let frame_count_max = stream.frame_count_max();
+    ///     let result = stream.write_chunks::<f32, _>(frame_count_max, |mut chunk, _offset| {
+    ///         for mut frame in chunk.frames_mut() {
+    ///             for c in 0..frame.channel_count() {
+    ///                 frame.set(c, phase.sin());
+    ///             }
+    ///             *phase += 0.01;
+    ///         }
+    ///     });
+    ///     if let Err(e) = result {
+    ///         println!("Error writing to stream: {}", e);
+    ///     }
+    /// }
+    /// ```
+    pub fn write_chunks<T, F>(&mut self, frame_count: usize, mut chunk_fn: F) -> Result<usize>
+    where
+        T: Sample,
+        F: FnMut(WriteChunk<'_, 'a, T>, usize),
+    {
+        let mut offset = 0;
+        while offset < frame_count {
+            let chunk_frames = self.begin_write(frame_count - offset)?;
+            if chunk_frames == 0 {
+                self.end_write()?;
+                break;
+            }
+            chunk_fn(
+                WriteChunk {
+                    writer: self,
+                    phantom: PhantomData,
+                },
+                offset,
+            );
+            self.end_write()?;
+            offset += chunk_frames;
+        }
+        Ok(offset)
+    }
+}
+
+/// One contiguous chunk of an output buffer, passed to the closure given to
+/// `OutStreamWriter::write_chunks()`. Samples are written as `T` and converted to the
+/// stream format if necessary. Frame indices are relative to the start of the chunk.
+pub struct WriteChunk<'c, 'a: 'c, T> {
+    writer: &'c mut OutStreamWriter<'a>,
+    phantom: PhantomData<T>,
+}
+
+impl<'c, 'a, T: Sample> WriteChunk<'c, 'a, T> {
+    /// The number of frames in this chunk.
+    pub fn frame_count(&self) -> usize {
+        self.writer.frame_count
+    }
+
+    /// The number of channels in the stream.
+    pub fn channel_count(&self) -> usize {
+        self.writer.channel_count()
+    }
+
+    /// The sample rate in Hertz.
+    pub fn sample_rate(&self) -> i32 {
+        self.writer.sample_rate()
+    }
+
+    /// Set a sample. See `OutStreamWriter::set_sample()`.
+    pub fn set(&mut self, channel: usize, frame: usize, sample: T) {
+        self.writer.set_sample(channel, frame, sample);
+    }
+
+    /// Iterate over the frames of the chunk. See `OutStreamWriter::frames_mut()`.
+    pub fn frames_mut(&mut self) -> FramesMut<'_, T> {
+        self.writer.frames_mut()
+    }
+
+    /// Iterate over the samples of one channel. See `OutStreamWriter::channel_samples_mut()`.
+    pub fn channel_samples_mut(&mut self, channel: usize) -> ChannelSamplesMut<'_, T> {
+        self.writer.channel_samples_mut(channel)
+    }
+}
+
+impl<'c, 'a, T: NativeSample> WriteChunk<'c, 'a, T> {
+    /// Get the samples of one channel as a slice. See `OutStreamWriter::channel_slice_mut()`.
+    pub fn channel_slice_mut(&mut self, channel: usize) -> result::Result<&mut [T], SliceError> {
+        self.writer.channel_slice_mut(channel)
+    }
+
+    /// Get the samples of all channels as an interleaved slice. See
+    /// `OutStreamWriter::interleaved_slice_mut()`.
+    pub fn interleaved_slice_mut(&mut self) -> result::Result<&mut [T], SliceError> {
+        self.writer.interleaved_slice_mut()
+    }
 }
 
 impl<'a> Drop for OutStreamWriter<'a> {
-    /// This will drop all of the frames from when you called `begin_write()`.
+    /// This commits the write if you called `begin_write()` without `end_write()`.
     ///
-    /// Errors are currently are just printed to the console and ignored.
+    /// Errors are recorded in the stream's `EventSink` as `Event::EndWriteError`, and
+    /// can be retrieved with `EventSink::take_last_end_error()`.
     ///
     /// # Errors
     ///
     /// * `Error::Streaming`
+    /// * `Error::Underflow`
     fn drop(&mut self) {
-        if self.write_started {
-            unsafe {
-                match raw::soundio_outstream_end_write(self.outstream) {
-                    0 => {}
-                    x => println!("Error writing outstream: {}", Error::from(x)),
-                }
-            }
+        if let Err(e) = self.end_write() {
+            self.event_sink.record(Event::EndWriteError(e));
         }
     }
 }
@@ -0,0 +1,130 @@
+use std::cell::UnsafeCell;
+use std::mem::MaybeUninit;
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+// A bounded multi-producer multi-consumer queue, from Dmitry Vyukov's design. Each slot has
+// a sequence number that says whether it is ready to be written or read for a given
+// position, so pushing and popping only need a compare-and-swap on the position.
+pub(crate) struct BoundedQueue<T> {
+    slots: Box<[Slot<T>]>,
+    mask: usize,
+    push_pos: AtomicUsize,
+    pop_pos: AtomicUsize,
+}
+
+struct Slot<T> {
+    sequence: AtomicUsize,
+    value: UnsafeCell<MaybeUninit<T>>,
+}
+
+// Access to each slot's value is serialised by its sequence number.
+unsafe impl<T: Send> Sync for BoundedQueue<T> {}
+
+impl<T> BoundedQueue<T> {
+    /// Create a queue that holds up to `capacity` values, rounded up to a power of two.
+    pub(crate) fn new(capacity: usize) -> BoundedQueue<T> {
+        let capacity = capacity.max(2).next_power_of_two();
+        let slots = (0..capacity)
+            .map(|i| Slot {
+                sequence: AtomicUsize::new(i),
+                value: UnsafeCell::new(MaybeUninit::uninit()),
+            })
+            .collect::<Vec<_>>()
+            .into_boxed_slice();
+        BoundedQueue {
+            slots,
+            mask: capacity - 1,
+            push_pos: AtomicUsize::new(0),
+            pop_pos: AtomicUsize::new(0),
+        }
+    }
+
+    /// Add a value to the queue, or give it back if the queue is full.
+    pub(crate) fn push(&self, value: T) -> Result<(), T> {
+        let mut pos = self.push_pos.load(Ordering::Relaxed);
+        loop {
+            let slot = &self.slots[pos & self.mask];
+            let sequence = slot.sequence.load(Ordering::Acquire);
+            let diff = sequence.wrapping_sub(pos) as isize;
+            if diff == 0 {
+                match self.push_pos.compare_exchange_weak(
+                    pos,
+                    pos.wrapping_add(1),
+                    Ordering::Relaxed,
+                    Ordering::Relaxed,
+                ) {
+                    Ok(_) => {
+                        unsafe {
+                            (*slot.value.get()).as_mut_ptr().write(value);
+                        }
+                        slot.sequence.store(pos.wrapping_add(1), Ordering::Release);
+                        return Ok(());
+                    }
+                    Err(current) => pos = current,
+                }
+            } else if diff < 0 {
+                return Err(value);
+            } else {
+                pos = self.push_pos.load(Ordering::Relaxed);
+            }
+        }
+    }
+
+    /// Remove the oldest value.
+    pub(crate) fn pop(&self) -> Option<T> {
+        let mut pos = self.pop_pos.load(Ordering::Relaxed);
+        loop {
+            let slot = &self.slots[pos & self.mask];
+            let sequence = slot.sequence.load(Ordering::Acquire);
+            let diff = sequence.wrapping_sub(pos.wrapping_add(1)) as isize;
+            if diff == 0 {
+                match self.pop_pos.compare_exchange_weak(
+                    pos,
+                    pos.wrapping_add(1),
+                    Ordering::Relaxed,
+                    Ordering::Relaxed,
+                ) {
+                    Ok(_) => {
+                        let value = unsafe { (*slot.value.get()).as_ptr().read() };
+                        slot.sequence
+                            .store(pos.wrapping_add(self.mask + 1), Ordering::Release);
+                        return Some(value);
+                    }
+                    Err(current) => pos = current,
+                }
+            } else if diff < 0 {
+                return None;
+            } else {
+                pos = self.pop_pos.load(Ordering::Relaxed);
+            }
+        }
+    }
+}
+
+impl<T> Drop for BoundedQueue<T> {
+    fn drop(&mut self) {
+        while self.pop().is_some() {}
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Arc;
+
+    #[test]
+    fn full_and_drop() {
+        let queue = BoundedQueue::new(2);
+        assert!(queue.push(Arc::new(1)).is_ok());
+        let value = Arc::new(2);
+        assert!(queue.push(value.clone()).is_ok());
+        match queue.push(Arc::new(3)) {
+            Err(v) => assert_eq!(*v, 3),
+            Ok(()) => panic!("Pushed to a full queue"),
+        }
+        assert_eq!(queue.pop().map(|v| *v), Some(1));
+        assert_eq!(Arc::strong_count(&value), 2);
+        drop(queue);
+        assert_eq!(Arc::strong_count(&value), 1);
+    }
+}
@@ -0,0 +1,310 @@
+extern crate libsoundio_sys as raw;
+
+use super::buffer::*;
+use super::context::*;
+use super::device::*;
+use super::error::*;
+
+use std::cmp::min;
+use std::mem;
+use std::os::raw::c_int;
+use std::ptr;
+use std::slice;
+use std::sync::Arc;
+
+/// A single-producer single-consumer lock-free ring buffer, using libsoundio's
+/// `SoundIoRingBuffer`. It is safe to use from read and write callbacks since
+/// reading and writing never block or allocate.
+///
+/// The buffer is created with `RingBuffer::new()` and then split into a `Producer`
+/// and a `Consumer`, which can be sent to different threads. It needs a `Context`
+/// because libsoundio only finds out the memory page size, which the buffer size is
+/// rounded to, when the first context is created.
+///
+/// libsoundio maps the memory of the ring buffer twice, one after the other, so the
+/// free and filled regions are always contiguous even when they wrap around the end of
+/// the buffer. This means `Producer::write_region()` and `Consumer::read_region()` can
+/// return plain slices.
+///
+/// # Examples
+///
+/// ```
+/// let ctx = soundio::Context::new();
+/// let rb = soundio::RingBuffer::new(&ctx, 4096).unwrap();
+/// let (mut producer, mut consumer) = rb.split();
+///
+/// assert_eq!(producer.write(&[0.1f32, 0.2, 0.3]), 3);
+///
+/// let mut samples = [0.0f32; 8];
+/// assert_eq!(consumer.read(&mut samples), 3);
+/// assert_eq!(&samples[..3], &[0.1, 0.2, 0.3]);
+/// ```
+pub struct RingBuffer {
+    inner: Arc<RingBufferInner>,
+}
+
+// The raw ring buffer, which is destroyed when both halves have been dropped.
+struct RingBufferInner {
+    ring_buffer: *mut raw::SoundIoRingBuffer,
+}
+
+// libsoundio's ring buffer uses atomics for the read and write offsets, and the
+// `Producer` and `Consumer` only use the functions that are allowed for their side.
+unsafe impl Send for RingBufferInner {}
+unsafe impl Sync for RingBufferInner {}
+
+impl Drop for RingBufferInner {
+    fn drop(&mut self) {
+        unsafe {
+            raw::soundio_ring_buffer_destroy(self.ring_buffer);
+        }
+    }
+}
+
+impl RingBuffer {
+    /// Create a ring buffer that can hold at least `capacity` bytes. The actual capacity
+    /// might be greater for alignment purposes; use `capacity()` to get it.
+    ///
+    /// The buffer doesn't borrow `ctx`, so it can outlive it.
+    ///
+    /// # Errors
+    ///
+    /// * `Error::NoMem` - the memory could not be allocated or mapped.
+    /// * `Error::Invalid` - `capacity` is 0 or too large.
+    pub fn new(ctx: &Context, capacity: usize) -> Result<RingBuffer> {
+        RingBuffer::create(ctx.raw(), capacity)
+    }
+
+    /// Create a ring buffer with the context of `device`. See `RingBuffer::new()`.
+    pub(crate) fn for_device(device: &Device, capacity: usize) -> Result<RingBuffer> {
+        RingBuffer::create(unsafe { (*device.device).soundio }, capacity)
+    }
+
+    fn create(soundio: *mut raw::SoundIo, capacity: usize) -> Result<RingBuffer> {
+        if capacity == 0 || capacity > c_int::MAX as usize / 2 {
+            return Err(Error::Invalid);
+        }
+
+        let ring_buffer = unsafe { raw::soundio_ring_buffer_create(soundio, capacity as c_int) };
+        if ring_buffer.is_null() {
+            return Err(Error::NoMem);
+        }
+
+        Ok(RingBuffer {
+            inner: Arc::new(RingBufferInner { ring_buffer }),
+        })
+    }
+
+    /// The capacity of the buffer in bytes.
+    pub fn capacity(&self) -> usize {
+        self.inner.capacity()
+    }
+
+    /// Split the ring buffer into the writing and reading halves.
+    pub fn split(self) -> (Producer, Consumer) {
+        (
+            Producer {
+                inner: self.inner.clone(),
+            },
+            Consumer { inner: self.inner },
+        )
+    }
+}
+
+impl RingBufferInner {
+    fn capacity(&self) -> usize {
+        unsafe { raw::soundio_ring_buffer_capacity(self.ring_buffer) as usize }
+    }
+
+    fn fill_count(&self) -> usize {
+        unsafe { raw::soundio_ring_buffer_fill_count(self.ring_buffer) as usize }
+    }
+
+    fn free_count(&self) -> usize {
+        unsafe { raw::soundio_ring_buffer_free_count(self.ring_buffer) as usize }
+    }
+}
+
+/// The writing half of a `RingBuffer`.
+pub struct Producer {
+    inner: Arc<RingBufferInner>,
+}
+
+impl Producer {
+    /// The capacity of the buffer in bytes.
+    pub fn capacity(&self) -> usize {
+        self.inner.capacity()
+    }
+
+    /// The number of bytes that can be written.
+    pub fn free_count(&self) -> usize {
+        self.inner.free_count()
+    }
+
+    /// The number of samples of type `T` that can be written.
+    pub fn free_samples<T: NativeSample>(&self) -> usize {
+        self.free_count() / mem::size_of::<T>()
+    }
+
+    /// The free part of the buffer, which is `free_count()` bytes long. After writing to it
+    /// call `advance()` to make the data available to the `Consumer`.
+    pub fn write_region(&mut self) -> &mut [u8] {
+        let len = self.free_count();
+        unsafe {
+            let ptr = raw::soundio_ring_buffer_write_ptr(self.inner.ring_buffer) as *mut u8;
+            slice::from_raw_parts_mut(ptr, len)
+        }
+    }
+
+    /// Make `count` bytes written to `write_region()` available for reading.
+    /// Panics if `count` is greater than `free_count()`.
+    pub fn advance(&mut self, count: usize) {
+        assert!(count <= self.free_count(), "Advanced past the free region");
+        unsafe {
+            raw::soundio_ring_buffer_advance_write_ptr(self.inner.ring_buffer, count as c_int);
+        }
+    }
+
+    /// Write as many samples from `samples` as will fit, and return the number written.
+    pub fn write<T: NativeSample>(&mut self, samples: &[T]) -> usize {
+        let count = min(samples.len(), self.free_samples::<T>());
+        let bytes = count * mem::size_of::<T>();
+        unsafe {
+            // The region might not be aligned for T, so copy it as bytes.
+            ptr::copy_nonoverlapping(
+                samples.as_ptr() as *const u8,
+                self.write_region().as_mut_ptr(),
+                bytes,
+            );
+        }
+        self.advance(bytes);
+        count
+    }
+
+    /// Discard everything in the buffer that hasn't been read yet.
+    pub fn clear(&mut self) {
+        unsafe {
+            raw::soundio_ring_buffer_clear(self.inner.ring_buffer);
+        }
+    }
+}
+
+/// The reading half of a `RingBuffer`.
+pub struct Consumer {
+    inner: Arc<RingBufferInner>,
+}
+
+impl Consumer {
+    /// The capacity of the buffer in bytes.
+    pub fn capacity(&self) -> usize {
+        self.inner.capacity()
+    }
+
+    /// The number of bytes that can be read.
+    pub fn fill_count(&self) -> usize {
+        self.inner.fill_count()
+    }
+
+    /// The number of samples of type `T` that can be read.
+    pub fn fill_samples<T: NativeSample>(&self) -> usize {
+        self.fill_count() / mem::size_of::<T>()
+    }
+
+    /// The filled part of the buffer, which is `fill_count()` bytes long. After reading it
+    /// call `advance()` to free the space for the `Producer`.
+    pub fn read_region(&self) -> &[u8] {
+        let len = self.fill_count();
+        unsafe {
+            let ptr = raw::soundio_ring_buffer_read_ptr(self.inner.ring_buffer) as *const u8;
+            slice::from_raw_parts(ptr, len)
+        }
+    }
+
+    /// Free `count` bytes that have been read from `read_region()`.
+    /// Panics if `count` is greater than `fill_count()`.
+    pub fn advance(&mut self, count: usize) {
+        assert!(
+            count <= self.fill_count(),
+            "Advanced past the filled region"
+        );
+        unsafe {
+            raw::soundio_ring_buffer_advance_read_ptr(self.inner.ring_buffer, count as c_int);
+        }
+    }
+
+    /// Read as many samples into `dst` as are available, and return the number read.
+    pub fn read<T: NativeSample>(&mut self, dst: &mut [T]) -> usize {
+        let count = min(dst.len(), self.fill_samples::<T>());
+        let bytes = count * mem::size_of::<T>();
+        unsafe {
+            // The region might not be aligned for T, so copy it as bytes.
+            ptr::copy_nonoverlapping(
+                self.read_region().as_ptr(),
+                dst.as_mut_ptr() as *mut u8,
+                bytes,
+            );
+        }
+        self.advance(bytes);
+        count
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::thread;
+
+    #[test]
+    fn wrap_around() {
+        let ctx = Context::new();
+        let rb = RingBuffer::new(&ctx, 64).unwrap();
+        let capacity = rb.capacity();
+        let (mut producer, mut consumer) = rb.split();
+
+        // Fill and empty the buffer a few times so that it wraps at odd offsets.
+        let samples: Vec<i16> = (0..(capacity / 2 - 3) as i16).collect();
+        let mut out = vec![0i16; samples.len()];
+        for _ in 0..5 {
+            assert_eq!(producer.write(&samples), samples.len());
+            assert_eq!(producer.free_count(), 6);
+            assert_eq!(consumer.fill_samples::<i16>(), samples.len());
+            assert_eq!(consumer.read(&mut out), samples.len());
+            assert_eq!(samples, out);
+        }
+        assert_eq!(consumer.fill_count(), 0);
+    }
+
+    #[test]
+    fn across_threads() {
+        let ctx = Context::new();
+        let (mut producer, mut consumer) = RingBuffer::new(&ctx, 256).unwrap().split();
+        let writer = thread::spawn(move || {
+            let mut next = 0u32;
+            while next < 10_000 {
+                next += producer.write(&[next]) as u32;
+            }
+        });
+
+        let mut expected = 0u32;
+        let mut buf = [0u32; 16];
+        while expected < 10_000 {
+            let n = consumer.read(&mut buf);
+            for &x in &buf[..n] {
+                assert_eq!(x, expected);
+                expected += 1;
+            }
+        }
+        writer.join().unwrap();
+    }
+
+    #[test]
+    fn invalid_capacity() {
+        let ctx = Context::new();
+        for &capacity in &[0, usize::MAX] {
+            match RingBuffer::new(&ctx, capacity) {
+                Err(Error::Invalid) => {}
+                r => panic!("Unexpected {:?}", r.map(|rb| rb.capacity())),
+            }
+        }
+    }
+}
@@ -83,17 +83,37 @@ impl u24 {
     pub fn max_value() -> u32 {
         0x00FF_FFFF
     }
+
+    // Convert from and to the 32-bit word that libsoundio stores 24-bit samples in.
+    #[inline]
+    pub(crate) fn from_word(v: u32) -> u24 {
+        u24(v & 0x00FF_FFFF)
+    }
+    #[inline]
+    pub(crate) fn to_word(self) -> u32 {
+        self.0
+    }
 }
 
 impl i24 {
     #[inline]
     pub fn min_value() -> i32 {
-        -0x008_0000
+        -0x0080_0000
     }
     #[inline]
     pub fn max_value() -> i32 {
         0x007F_FFFF
     }
+
+    // Convert from and to the 32-bit word that libsoundio stores 24-bit samples in.
+    #[inline]
+    pub(crate) fn from_word(v: i32) -> i24 {
+        i24((v << 8) >> 8)
+    }
+    #[inline]
+    pub(crate) fn to_word(self) -> i32 {
+        self.0
+    }
 }
 
 macro_rules! impl_to_methods {
@@ -158,17 +178,15 @@ macro_rules! impl_raw_methods_24 {
         }
         unsafe fn from_raw_be(ptr: *const u8) -> Self {
             // TODO: This seems like a suboptimal implementation.
-            $ty_24(((u32::from_raw_le(ptr.offset(3)) << 8) as $ty_32) >> 8)
+            $ty_24(((u32::from_raw_be(ptr) << 8) as $ty_32) >> 8)
         }
+        // The samples are stored in the low three bytes of a 32-bit word, so for
+        // big endian they are at offsets 1-3.
         unsafe fn to_raw_le(v: Self, ptr: *mut u8) {
-            *ptr = (v.0 & 0xFF) as u8;
-            *ptr.offset(1) = ((v.0 >> 8) & 0xFF) as u8;
-            *ptr.offset(2) = ((v.0 >> 16) & 0xFF) as u8;
+            u32::to_raw_le(v.0 as u32, ptr)
         }
         unsafe fn to_raw_be(v: Self, ptr: *mut u8) {
-            *ptr = ((v.0 >> 16) & 0xFF) as u8;
-            *ptr.offset(1) = ((v.0 >> 8) & 0xFF) as u8;
-            *ptr.offset(2) = (v.0 & 0xFF) as u8;
+            u32::to_raw_be(v.0 as u32, ptr)
         }
     };
 }
@@ -606,4 +624,18 @@ mod tests {
             }
         }
     }
+
+    #[test]
+    fn raw_24_bit_big_endian() {
+        let mut buf = [0u8; 4];
+        unsafe {
+            i24::to_raw_be(i24::from_i16(-2), buf.as_mut_ptr());
+            assert_eq!(buf, [0xFF, 0xFF, 0xFE, 0x00]);
+            assert_eq!(i16::from_i24(i24::from_raw_be(buf.as_ptr())), -2);
+
+            u24::to_raw_be(u24::from_u16(0x1234), buf.as_mut_ptr());
+            assert_eq!(buf, [0x00, 0x12, 0x34, 0x00]);
+            assert_eq!(u16::from_u24(u24::from_raw_be(buf.as_ptr())), 0x1234);
+        }
+    }
 }
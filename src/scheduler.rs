@@ -0,0 +1,324 @@
+use super::outstream::*;
+use super::queue::*;
+
+use std::result;
+use std::sync::Arc;
+use std::time::Instant;
+
+/// When a command sent with `Scheduler::schedule()` should take effect.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum ScheduleTime {
+    /// At the start of the next write.
+    Now,
+    /// At a frame position of the stream. See `OutStreamWriter::frame_position()` and
+    /// `OutStreamHandle::frame_position()`.
+    Frame(u64),
+    /// At the frame that is heard at the given time. The time is converted to a frame
+    /// with `OutStreamWriter::timestamp()` when the command is received.
+    Time(Instant),
+}
+
+/// What `Dispatcher::dispatch()` asks the render closure to do.
+#[derive(Debug)]
+pub enum Dispatch<C> {
+    /// Apply a command. `offset` is the frame of the current write that it applies
+    /// from. Commands that are late are applied at offset 0.
+    Command {
+        /// The frame offset in the current write.
+        offset: usize,
+        /// The command that was scheduled.
+        command: C,
+    },
+    /// Render frames `start..end` of the current write.
+    Render {
+        /// The first frame to render.
+        start: usize,
+        /// One past the last frame to render.
+        end: usize,
+    },
+}
+
+/// The sending half of a command scheduler for an output stream. It can be cloned and
+/// used from any thread to send commands, such as "start a voice" or "change the gain",
+/// that the write callback applies at an exact frame. The commands are received by
+/// the `Dispatcher` that was created with it.
+///
+/// Sending never blocks or allocates, so it can also be used from other audio threads.
+///
+/// # Examples
+///
+/// ```
+/// # fn foo() -> Result<(), soundio::Error> {
+/// use soundio::{Dispatch, ScheduleTime, Scheduler};
+///
+/// enum Command {
+///     SetGain(f32),
+/// }
+///
+/// let mut ctx = soundio::Context::new();
+/// ctx.connect_backend(soundio::Backend::Dummy)?;
+/// ctx.flush_events();
+/// let dev = ctx.default_output_device()?;
+///
+/// let (scheduler, mut dispatcher) = Scheduler::new(64);
+/// let mut gain = 1.0f32;
+/// let mut stream = dev
+///     .outstream_builder()
+///     .write_callback(move |stream: &mut soundio::OutStreamWriter| {
+///         let frame_count_max = stream.frame_count_max();
+///         stream.begin_write(frame_count_max).unwrap();
+///         dispatcher.dispatch(stream, |stream, dispatch| match dispatch {
+///             Dispatch::Command {
+///                 command: Command::SetGain(g),
+///                 ..
+///             } => gain = g,
+///             Dispatch::Render { start, end } => {
+///                 for f in start..end {
+///                     for c in 0..stream.channel_count() {
+///                         stream.set_sample(c, f, 0.1 * gain);
+///                     }
+///                 }
+///             }
+///         });
+///     })
+///     .open()?;
+/// stream.start()?;
+///
+/// // Halve the gain exactly one second from now in the stream.
+/// let position = stream.handle().frame_position();
+/// let frame = position + stream.sample_rate() as u64;
+/// if scheduler
+///     .schedule(ScheduleTime::Frame(frame), Command::SetGain(0.5))
+///     .is_err()
+/// {
+///     println!("The scheduler is full");
+/// }
+/// # Ok(())
+/// # }
+/// ```
+pub struct Scheduler<C> {
+    queue: Arc<BoundedQueue<(ScheduleTime, C)>>,
+}
+
+impl<C> Clone for Scheduler<C> {
+    fn clone(&self) -> Scheduler<C> {
+        Scheduler {
+            queue: self.queue.clone(),
+        }
+    }
+}
+
+impl<C: Send> Scheduler<C> {
+    /// Create a scheduler and its `Dispatcher`. Up to `capacity` commands can be waiting
+    /// to be received and up to `capacity` received commands can be waiting for their
+    /// frame. The capacity of the queue is rounded up to a power of two, and a
+    /// `capacity` of 0 is treated as 1.
+    pub fn new(capacity: usize) -> (Scheduler<C>, Dispatcher<C>) {
+        let queue = Arc::new(BoundedQueue::new(capacity));
+        (
+            Scheduler {
+                queue: queue.clone(),
+            },
+            Dispatcher {
+                queue,
+                // `receive()` only takes commands while `pending` has space.
+                pending: Vec::with_capacity(capacity.max(1)),
+            },
+        )
+    }
+
+    /// Send a command to be applied at `time`. If the queue is full the command is
+    /// returned.
+    pub fn schedule(&self, time: ScheduleTime, command: C) -> result::Result<(), C> {
+        self.queue.push((time, command)).map_err(|(_, c)| c)
+    }
+}
+
+/// The receiving half of a `Scheduler`, which is used in the write callback. See
+/// `Scheduler` for an example.
+pub struct Dispatcher<C> {
+    queue: Arc<BoundedQueue<(ScheduleTime, C)>>,
+    // Received commands with their frame, sorted by frame. It never grows past its
+    // initial capacity so that the write callback doesn't allocate.
+    pending: Vec<(u64, C)>,
+}
+
+impl<C> Dispatcher<C> {
+    /// Split the current write into the commands that are due in it and the ranges of
+    /// frames between them, and call `dispatch_fn` with each in order. You must have
+    /// called `begin_write()` first, otherwise this panics.
+    ///
+    /// For example if a command is due at frame 100 of a 256 frame write, `dispatch_fn`
+    /// is called with `Render { start: 0, end: 100 }`, then `Command { offset: 100, .. }`
+    /// and then `Render { start: 100, end: 256 }`. Commands for the same frame are
+    /// applied in the order they were sent, and commands for later writes wait.
+    pub fn dispatch<'a, F>(&mut self, writer: &mut OutStreamWriter<'a>, mut dispatch_fn: F)
+    where
+        F: FnMut(&mut OutStreamWriter<'a>, Dispatch<C>),
+    {
+        let start = writer.frame_position();
+        let frame_count = writer.frame_count();
+
+        // The latency is only measured if a command was scheduled by time.
+        let mut timestamp = None;
+        self.receive(start, |time| {
+            timestamp
+                .get_or_insert_with(|| writer.timestamp().ok())
+                .map_or(start, |ts| ts.frame_at(time))
+        });
+        self.dispatch_frames(start, frame_count, |dispatch| dispatch_fn(writer, dispatch));
+    }
+
+    /// The number of received commands that are waiting for their frame.
+    pub fn pending_count(&self) -> usize {
+        self.pending.len()
+    }
+
+    // Move commands from the queue to `pending`, while there is space.
+    fn receive<R>(&mut self, now: u64, mut frame_at: R)
+    where
+        R: FnMut(Instant) -> u64,
+    {
+        while self.pending.len() < self.pending.capacity() {
+            let (time, command) = match self.queue.pop() {
+                Some(x) => x,
+                None => break,
+            };
+            let frame = match time {
+                ScheduleTime::Now => now,
+                ScheduleTime::Frame(frame) => frame,
+                ScheduleTime::Time(time) => frame_at(time),
+            };
+            let index = self.pending.partition_point(|&(f, _)| f <= frame);
+            self.pending.insert(index, (frame, command));
+        }
+    }
+
+    fn dispatch_frames<F>(&mut self, start: u64, frame_count: usize, mut dispatch_fn: F)
+    where
+        F: FnMut(Dispatch<C>),
+    {
+        let end = start + frame_count as u64;
+        let due = self.pending.partition_point(|&(f, _)| f < end);
+        let mut rendered = 0;
+        for (frame, command) in self.pending.drain(..due) {
+            let offset = frame.saturating_sub(start) as usize;
+            if offset > rendered {
+                dispatch_fn(Dispatch::Render {
+                    start: rendered,
+                    end: offset,
+                });
+                rendered = offset;
+            }
+            dispatch_fn(Dispatch::Command { offset, command });
+        }
+        if rendered < frame_count {
+            dispatch_fn(Dispatch::Render {
+                start: rendered,
+                end: frame_count,
+            });
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::thread;
+
+    fn run(dispatcher: &mut Dispatcher<u32>, start: u64, frame_count: usize) -> Vec<String> {
+        let mut log = Vec::new();
+        dispatcher.receive(start, |_| 1000);
+        dispatcher.dispatch_frames(start, frame_count, |d| {
+            log.push(match d {
+                Dispatch::Command { offset, command } => format!("{}@{}", command, offset),
+                Dispatch::Render { start, end } => format!("{}..{}", start, end),
+            })
+        });
+        log
+    }
+
+    #[test]
+    fn split_at_commands() {
+        let (scheduler, mut dispatcher) = Scheduler::new(8);
+        scheduler.schedule(ScheduleTime::Frame(300), 3).unwrap();
+        scheduler.schedule(ScheduleTime::Frame(100), 1).unwrap();
+        scheduler.schedule(ScheduleTime::Frame(100), 2).unwrap();
+        scheduler.schedule(ScheduleTime::Frame(256), 4).unwrap();
+        assert_eq!(
+            run(&mut dispatcher, 0, 256),
+            ["0..100", "1@100", "2@100", "100..256"]
+        );
+        assert_eq!(dispatcher.pending_count(), 2);
+        assert_eq!(
+            run(&mut dispatcher, 256, 256),
+            ["4@0", "0..44", "3@44", "44..256"]
+        );
+    }
+
+    #[test]
+    fn late_now_and_time() {
+        let (scheduler, mut dispatcher) = Scheduler::new(8);
+        scheduler.schedule(ScheduleTime::Frame(10), 1).unwrap();
+        scheduler.schedule(ScheduleTime::Now, 2).unwrap();
+        scheduler
+            .schedule(ScheduleTime::Time(Instant::now()), 3)
+            .unwrap();
+        assert_eq!(
+            run(&mut dispatcher, 900, 200),
+            ["1@0", "2@0", "0..100", "3@100", "100..200"]
+        );
+    }
+
+    #[test]
+    fn bounded() {
+        let (scheduler, mut dispatcher) = Scheduler::new(2);
+        scheduler.schedule(ScheduleTime::Frame(1000), 1).unwrap();
+        scheduler.schedule(ScheduleTime::Frame(1000), 2).unwrap();
+        assert_eq!(scheduler.schedule(ScheduleTime::Now, 3), Err(3));
+        assert_eq!(run(&mut dispatcher, 0, 10), ["0..10"]);
+
+        // Pending is full, so this waits in the queue until there is space.
+        scheduler.schedule(ScheduleTime::Now, 3).unwrap();
+        assert_eq!(run(&mut dispatcher, 10, 10), ["0..10"]);
+        assert_eq!(run(&mut dispatcher, 1000, 10), ["1@0", "2@0", "0..10"]);
+        assert_eq!(run(&mut dispatcher, 1010, 10), ["3@0", "0..10"]);
+    }
+
+    #[test]
+    fn zero_capacity() {
+        let (scheduler, mut dispatcher) = Scheduler::new(0);
+        scheduler.schedule(ScheduleTime::Frame(5), 1).unwrap();
+        assert_eq!(run(&mut dispatcher, 0, 10), ["0..5", "1@5", "5..10"]);
+    }
+
+    #[test]
+    fn from_threads() {
+        let (scheduler, mut dispatcher) = Scheduler::new(64);
+        let threads: Vec<_> = (0..4)
+            .map(|t| {
+                let scheduler = scheduler.clone();
+                thread::spawn(move || {
+                    for i in 0..8 {
+                        scheduler
+                            .schedule(ScheduleTime::Frame(i * 4 + t), (i * 4 + t) as u32)
+                            .unwrap();
+                    }
+                })
+            })
+            .collect();
+        for t in threads {
+            t.join().unwrap();
+        }
+
+        let mut commands = Vec::new();
+        dispatcher.receive(0, |_| 0);
+        dispatcher.dispatch_frames(0, 32, |d| {
+            if let Dispatch::Command { offset, command } = d {
+                assert_eq!(offset as u32, command);
+                commands.push(command);
+            }
+        });
+        assert_eq!(commands, (0..32).collect::<Vec<_>>());
+    }
+}
@@ -0,0 +1,116 @@
+use std::time::{Duration, Instant};
+
+/// A point on a stream's timeline: the frame position of a buffer in the stream and the
+/// estimated time at which that frame is heard (for output) or was captured (for input).
+///
+/// It is obtained in the callbacks from `OutStreamWriter::timestamp()` and
+/// `InStreamReader::timestamp()`. The time is estimated from the latency reported by
+/// the backend and `Instant::now()`, so it is only as accurate as the backend's latency.
+///
+/// # Examples
+///
+/// ```
+/// use std::time::Instant;
+///
+/// fn write_callback(stream: &mut soundio::OutStreamWriter, click_at: Instant) {
+///     let frame_count_max = stream.frame_count_max();
+///     stream.begin_write(frame_count_max).unwrap();
+///     let timestamp = stream.timestamp().unwrap();
+///
+///     // The offset of the click in this buffer, if it is in it.
+///     let click_frame = timestamp.frame_at(click_at);
+///     if click_frame >= timestamp.frame
+///         && click_frame < timestamp.frame + stream.frame_count() as u64
+///     {
+///         let offset = (click_frame - timestamp.frame) as usize;
+///         for c in 0..stream.channel_count() {
+///             stream.set_sample(c, offset, 1.0f32);
+///         }
+///     }
+/// }
+/// ```
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct StreamTimestamp {
+    /// The number of frames written to or read from the stream before this point.
+    pub frame: u64,
+    /// The estimated time at which `frame` is heard or was captured.
+    pub time: Instant,
+    /// The sample rate of the stream.
+    pub sample_rate: i32,
+}
+
+impl StreamTimestamp {
+    /// The estimated time of another frame, which may be before or after `frame`.
+    pub fn time_of(&self, frame: u64) -> Instant {
+        if frame >= self.frame {
+            self.time + self.frames_to_duration(frame - self.frame)
+        } else {
+            let earlier = self.frames_to_duration(self.frame - frame);
+            self.time.checked_sub(earlier).unwrap_or(self.time)
+        }
+    }
+
+    /// The frame that is heard or captured at `time`, rounded to the nearest frame. Times
+    /// before the start of the stream give frame 0.
+    pub fn frame_at(&self, time: Instant) -> u64 {
+        if time >= self.time {
+            self.frame + self.duration_to_frames(time - self.time)
+        } else {
+            self.frame
+                .saturating_sub(self.duration_to_frames(self.time - time))
+        }
+    }
+
+    fn frames_to_duration(&self, frames: u64) -> Duration {
+        Duration::from_secs_f64(frames as f64 / f64::from(self.sample_rate.max(1)))
+    }
+
+    fn duration_to_frames(&self, duration: Duration) -> u64 {
+        (duration.as_secs_f64() * f64::from(self.sample_rate)).round() as u64
+    }
+}
+
+/// A timestamp for a buffer at `frame`, whose first frame is `offset` seconds after now.
+/// Negative offsets are in the past.
+pub(crate) fn timestamp_from_now(frame: u64, offset: f64, sample_rate: i32) -> StreamTimestamp {
+    let now = Instant::now();
+    // A NaN offset is treated as 0.
+    let magnitude = Duration::from_secs_f64(offset.abs().max(0.0));
+    let time = if offset >= 0.0 {
+        now + magnitude
+    } else {
+        now.checked_sub(magnitude).unwrap_or(now)
+    };
+    StreamTimestamp {
+        frame,
+        time,
+        sample_rate,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn frames_and_times() {
+        let ts = timestamp_from_now(48_000, 0.5, 48_000);
+        assert_eq!(ts.time_of(48_000), ts.time);
+        assert_eq!(ts.time_of(96_000), ts.time + Duration::from_secs(1));
+        assert_eq!(ts.time_of(24_000) + Duration::from_millis(500), ts.time);
+
+        assert_eq!(ts.frame_at(ts.time), 48_000);
+        assert_eq!(ts.frame_at(ts.time + Duration::from_millis(10)), 48_480);
+        assert_eq!(ts.frame_at(ts.time - Duration::from_millis(10)), 47_520);
+        for frame in [0, 1, 47_999, 48_001, 1_000_000].iter() {
+            assert_eq!(ts.frame_at(ts.time_of(*frame)), *frame);
+        }
+    }
+
+    #[test]
+    fn before_the_start() {
+        let ts = timestamp_from_now(100, -0.25, 1000);
+        assert!(ts.time <= Instant::now());
+        assert_eq!(ts.frame_at(ts.time - Duration::from_secs(1)), 0);
+    }
+}
@@ -0,0 +1,344 @@
+use super::buffer::*;
+use super::error::*;
+use super::handle::*;
+use super::instream::*;
+use super::outstream::*;
+
+use std::marker::PhantomData;
+use std::ptr;
+use std::result;
+
+/// An output stream whose format is fixed to `T::FORMAT` when it is opened, so the
+/// write callback gets `T` samples without any conversion.
+///
+/// It is opened with `OutStreamBuilder::open_typed()`, which fails with
+/// `Error::UnsupportedFormat` if the device doesn't support `T::FORMAT`. Only
+/// the types that libsoundio can store directly (see `NativeSample`) can be used.
+///
+/// # Examples
+///
+/// ```
+/// # fn foo() -> Result<(), soundio::Error> {
+/// let mut ctx = soundio::Context::new();
+/// ctx.connect_backend(soundio::Backend::Dummy)?;
+/// ctx.flush_events();
+/// let dev = ctx.default_output_device()?;
+///
+/// let mut stream = dev
+///     .outstream_builder()
+///     .open_typed(|stream: &mut soundio::TypedOutStreamWriter<f32>| {
+///         let frame_count_max = stream.frame_count_max();
+///         stream.begin_write(frame_count_max).unwrap();
+///         match stream.interleaved_slice_mut() {
+///             Ok(samples) => {
+///                 for s in samples {
+///                     *s = 0.0;
+///                 }
+///             }
+///             Err(_) => {
+///                 for c in 0..stream.channel_count() {
+///                     for f in 0..stream.frame_count() {
+///                         stream.set(c, f, 0.0);
+///                     }
+///                 }
+///             }
+///         }
+///     })?;
+/// stream.start()?;
+/// # Ok(())
+/// # }
+/// ```
+pub struct TypedOutStream<'a, T: NativeSample> {
+    stream: OutStream<'a>,
+    phantom: PhantomData<T>,
+}
+
+impl<'a, T: NativeSample> TypedOutStream<'a, T> {
+    pub(crate) fn new(stream: OutStream<'a>) -> TypedOutStream<'a, T> {
+        TypedOutStream {
+            stream,
+            phantom: PhantomData,
+        }
+    }
+
+    /// Start the stream. See `OutStream::start()`.
+    pub fn start(&mut self) -> Result<()> {
+        self.stream.start()
+    }
+
+    /// Pause or unpause the stream. See `OutStream::pause()`.
+    pub fn pause(&mut self, pause: bool) -> Result<()> {
+        self.stream.pause(pause)
+    }
+
+    /// The sample rate in frames per second.
+    pub fn sample_rate(&self) -> i32 {
+        self.stream.sample_rate()
+    }
+
+    /// Get a handle that can control the stream from other threads. See `OutStreamHandle`.
+    pub fn handle(&self) -> OutStreamHandle {
+        self.stream.handle()
+    }
+
+    /// The underlying stream.
+    pub fn stream(&self) -> &OutStream<'a> {
+        &self.stream
+    }
+
+    /// The underlying stream, for example to call `OutStream::set_volume()`.
+    pub fn stream_mut(&mut self) -> &mut OutStream<'a> {
+        &mut self.stream
+    }
+}
+
+/// `TypedOutStreamWriter` is passed to the write callback of a `TypedOutStream`. It is
+/// an `OutStreamWriter` whose samples are always `T`, so none of its functions need to
+/// check or convert the format.
+pub struct TypedOutStreamWriter<'w, 'a: 'w, T: NativeSample> {
+    writer: &'w mut OutStreamWriter<'a>,
+    phantom: PhantomData<T>,
+}
+
+impl<'w, 'a, T: NativeSample> TypedOutStreamWriter<'w, 'a, T> {
+    pub(crate) fn new(writer: &'w mut OutStreamWriter<'a>) -> TypedOutStreamWriter<'w, 'a, T> {
+        TypedOutStreamWriter {
+            writer,
+            phantom: PhantomData,
+        }
+    }
+
+    /// Start a write. See `OutStreamWriter::begin_write()`.
+    pub fn begin_write(&mut self, frame_count: usize) -> Result<usize> {
+        self.writer.begin_write(frame_count)
+    }
+
+    /// Commit the write. See `OutStreamWriter::end_write()`.
+    pub fn end_write(&mut self) -> Result<()> {
+        self.writer.end_write()
+    }
+
+    /// See `OutStreamWriter::frame_count_min()`.
+    pub fn frame_count_min(&self) -> usize {
+        self.writer.frame_count_min()
+    }
+
+    /// See `OutStreamWriter::frame_count_max()`.
+    pub fn frame_count_max(&self) -> usize {
+        self.writer.frame_count_max()
+    }
+
+    /// The number of frames in the current write. Panics if you haven't called
+    /// `begin_write()` yet.
+    pub fn frame_count(&self) -> usize {
+        self.writer.frame_count()
+    }
+
+    /// The number of channels in the stream.
+    pub fn channel_count(&self) -> usize {
+        self.writer.channel_count()
+    }
+
+    /// The sample rate in frames per second.
+    pub fn sample_rate(&self) -> i32 {
+        self.writer.sample_rate()
+    }
+
+    /// Set one sample. This panics if the `channel` or `frame` are out of range or if you
+    /// haven't called `begin_write()` yet.
+    pub fn set(&mut self, channel: usize, frame: usize, sample: T) {
+        assert!(channel < self.channel_count(), "Channel out of range");
+        assert!(frame < self.frame_count(), "Frame out of range");
+
+        let area = &self.writer.channel_areas()[channel];
+        unsafe {
+            let ptr = area.ptr.add(frame * area.step as usize) as *mut T;
+            ptr::write_unaligned(ptr, sample);
+        }
+    }
+
+    /// The samples of one channel. See `OutStreamWriter::channel_slice_mut()`.
+    pub fn channel_slice_mut(&mut self, channel: usize) -> result::Result<&mut [T], SliceError> {
+        self.writer.channel_slice_mut(channel)
+    }
+
+    /// The samples of all channels, interleaved. See
+    /// `OutStreamWriter::interleaved_slice_mut()`.
+    pub fn interleaved_slice_mut(&mut self) -> result::Result<&mut [T], SliceError> {
+        self.writer.interleaved_slice_mut()
+    }
+
+    /// Iterate over the frames of the write. See `OutStreamWriter::frames_mut()`.
+    pub fn frames_mut(&mut self) -> FramesMut<'_, T> {
+        self.writer.frames_mut()
+    }
+
+    /// Write `frame_count` frames in chunks. See `OutStreamWriter::write_chunks()`.
+    pub fn write_chunks<F>(&mut self, frame_count: usize, chunk_fn: F) -> Result<usize>
+    where
+        F: FnMut(WriteChunk<'_, 'a, T>, usize),
+    {
+        self.writer.write_chunks(frame_count, chunk_fn)
+    }
+
+    /// The untyped writer, for the functions that are not repeated here such as
+    /// `OutStreamWriter::get_latency()` and `OutStreamWriter::pause()`.
+    pub fn writer(&mut self) -> &mut OutStreamWriter<'a> {
+        self.writer
+    }
+}
+
+/// An input stream whose format is fixed to `T::FORMAT` when it is opened, so the
+/// read callback gets `T` samples without any conversion.
+///
+/// It is opened with `InStreamBuilder::open_typed()`, which fails with
+/// `Error::UnsupportedFormat` if the device doesn't support `T::FORMAT`.
+pub struct TypedInStream<'a, T: NativeSample> {
+    stream: InStream<'a>,
+    phantom: PhantomData<T>,
+}
+
+impl<'a, T: NativeSample> TypedInStream<'a, T> {
+    pub(crate) fn new(stream: InStream<'a>) -> TypedInStream<'a, T> {
+        TypedInStream {
+            stream,
+            phantom: PhantomData,
+        }
+    }
+
+    /// Start the stream. See `InStream::start()`.
+    pub fn start(&mut self) -> Result<()> {
+        self.stream.start()
+    }
+
+    /// Pause or unpause the stream. See `InStream::pause()`.
+    pub fn pause(&mut self, pause: bool) -> Result<()> {
+        self.stream.pause(pause)
+    }
+
+    /// The sample rate in frames per second.
+    pub fn sample_rate(&self) -> i32 {
+        self.stream.sample_rate()
+    }
+
+    /// Get a handle that can control the stream from other threads. See `InStreamHandle`.
+    pub fn handle(&self) -> InStreamHandle {
+        self.stream.handle()
+    }
+
+    /// The underlying stream.
+    pub fn stream(&self) -> &InStream<'a> {
+        &self.stream
+    }
+
+    /// The underlying stream.
+    pub fn stream_mut(&mut self) -> &mut InStream<'a> {
+        &mut self.stream
+    }
+}
+
+/// `TypedInStreamReader` is passed to the read callback of a `TypedInStream`. It is
+/// an `InStreamReader` whose samples are always `T`, so none of its functions need to
+/// check or convert the format.
+pub struct TypedInStreamReader<'r, 'a: 'r, T: NativeSample> {
+    reader: &'r mut InStreamReader<'a>,
+    phantom: PhantomData<T>,
+}
+
+impl<'r, 'a, T: NativeSample> TypedInStreamReader<'r, 'a, T> {
+    pub(crate) fn new(reader: &'r mut InStreamReader<'a>) -> TypedInStreamReader<'r, 'a, T> {
+        TypedInStreamReader {
+            reader,
+            phantom: PhantomData,
+        }
+    }
+
+    /// Start a read. See `InStreamReader::begin_read()`.
+    pub fn begin_read(&mut self, frame_count: usize) -> Result<usize> {
+        self.reader.begin_read(frame_count)
+    }
+
+    /// Finish the read. See `InStreamReader::end_read()`.
+    pub fn end_read(&mut self) -> Result<()> {
+        self.reader.end_read()
+    }
+
+    /// See `InStreamReader::frame_count_min()`.
+    pub fn frame_count_min(&self) -> usize {
+        self.reader.frame_count_min()
+    }
+
+    /// See `InStreamReader::frame_count_max()`.
+    pub fn frame_count_max(&self) -> usize {
+        self.reader.frame_count_max()
+    }
+
+    /// The number of frames in the current read. Panics if you haven't called
+    /// `begin_read()` yet.
+    pub fn frame_count(&self) -> usize {
+        self.reader.frame_count()
+    }
+
+    /// The number of channels in the stream.
+    pub fn channel_count(&self) -> usize {
+        self.reader.channel_count()
+    }
+
+    /// The sample rate in frames per second.
+    pub fn sample_rate(&self) -> i32 {
+        self.reader.sample_rate()
+    }
+
+    /// Returns true if the current read is a hole. See `InStreamReader::is_hole()`.
+    pub fn is_hole(&self) -> bool {
+        self.reader.is_hole()
+    }
+
+    /// Get one sample. This panics if the `channel` or `frame` are out of range, if the
+    /// read is a hole or if you haven't called `begin_read()` yet.
+    pub fn get(&self, channel: usize, frame: usize) -> T {
+        assert!(!self.is_hole(), "Captured data has a hole");
+        assert!(channel < self.channel_count(), "Channel out of range");
+        assert!(frame < self.frame_count(), "Frame out of range");
+
+        let area = &self.reader.channel_areas()[channel];
+        unsafe {
+            let ptr = area.ptr.add(frame * area.step as usize) as *const T;
+            ptr::read_unaligned(ptr)
+        }
+    }
+
+    /// The samples of one channel. See `InStreamReader::channel_slice()`.
+    pub fn channel_slice(&self, channel: usize) -> result::Result<&[T], SliceError> {
+        self.reader.channel_slice(channel)
+    }
+
+    /// The samples of all channels, interleaved. See `InStreamReader::interleaved_slice()`.
+    pub fn interleaved_slice(&self) -> result::Result<&[T], SliceError> {
+        self.reader.interleaved_slice()
+    }
+
+    /// A view of the samples of one channel. See `InStreamReader::channel_view()`.
+    pub fn channel_view(&self, channel: usize) -> result::Result<ChannelView<'_, T>, SliceError> {
+        self.reader.channel_view(channel)
+    }
+
+    /// Iterate over the frames of the read. See `InStreamReader::frames()`.
+    pub fn frames(&self) -> Frames<'_, T> {
+        self.reader.frames()
+    }
+
+    /// Read `frame_count` frames in chunks. See `InStreamReader::read_chunks()`.
+    pub fn read_chunks<F>(&mut self, frame_count: usize, chunk_fn: F) -> Result<usize>
+    where
+        F: FnMut(ReadChunk<'_, 'a, T>, usize),
+    {
+        self.reader.read_chunks(frame_count, chunk_fn)
+    }
+
+    /// The untyped reader, for the functions that are not repeated here such as
+    /// `InStreamReader::get_latency()` and `InStreamReader::pause()`.
+    pub fn reader(&mut self) -> &mut InStreamReader<'a> {
+        self.reader
+    }
+}
@@ -32,7 +32,7 @@ impl From<DeviceAim> for raw::SoundIoDeviceAim {
 
 /// Devices report their supported sample rates as ranges. For non-range sample
 /// rates `min` and `max` are the same.
-#[derive(Debug, Copy, Clone)]
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
 pub struct SampleRateRange {
     pub min: i32,
     pub max: i32,
@@ -0,0 +1,202 @@
+extern crate libsoundio_sys as raw;
+
+use super::buffer::*;
+use super::error::*;
+use super::format::*;
+
+use std::sync::atomic::{AtomicBool, AtomicU32, Ordering};
+
+/// The time in seconds that the software gain takes to move from silence to full volume.
+/// Smaller changes take proportionally less time.
+const RAMP_TIME: f64 = 0.02;
+
+/// Returns `Error::Invalid` unless `volume` is between 0.0 and 1.0.
+pub(crate) fn check_volume(volume: f32) -> Result<()> {
+    if (0.0..=1.0).contains(&volume) {
+        Ok(())
+    } else {
+        Err(Error::Invalid)
+    }
+}
+
+/// The volume of an output stream, shared between the stream, its writer and its handles.
+///
+/// The volume is set by the backend if possible. Otherwise it is applied by
+/// `GainRamp` when each write ends, which is called the software volume.
+pub(crate) struct VolumeControl {
+    // The requested volume as `f32` bits.
+    volume: AtomicU32,
+    software: AtomicBool,
+}
+
+impl VolumeControl {
+    pub(crate) fn new(volume: f32, software: bool) -> VolumeControl {
+        VolumeControl {
+            volume: AtomicU32::new(volume.to_bits()),
+            software: AtomicBool::new(software),
+        }
+    }
+
+    pub(crate) fn volume(&self) -> f32 {
+        f32::from_bits(self.volume.load(Ordering::Relaxed))
+    }
+
+    pub(crate) fn is_software(&self) -> bool {
+        self.software.load(Ordering::Relaxed)
+    }
+
+    /// Set the volume with `set_hardware`, unless the software volume is in use. If the
+    /// backend or device doesn't support setting the volume switch to the software volume.
+    pub(crate) fn set<F>(&self, volume: f32, set_hardware: F) -> Result<()>
+    where
+        F: FnOnce(f64) -> Result<()>,
+    {
+        check_volume(volume)?;
+        self.volume.store(volume.to_bits(), Ordering::Relaxed);
+        if self.is_software() {
+            return Ok(());
+        }
+        match set_hardware(f64::from(volume)) {
+            Err(Error::IncompatibleBackend) | Err(Error::IncompatibleDevice) => {
+                self.software.store(true, Ordering::Relaxed);
+                Ok(())
+            }
+            r => r,
+        }
+    }
+}
+
+/// The gain applied by the software volume. It is only used on the audio thread.
+///
+/// Jumping straight to a new volume causes a click, and a series of jumps (e.g. from a
+/// slider) causes "zipper noise", so the gain moves towards the requested volume
+/// linearly, by at most `1 / (RAMP_TIME * sample_rate)` per frame.
+pub(crate) struct GainRamp {
+    gain: f64,
+}
+
+impl GainRamp {
+    pub(crate) fn new(gain: f32) -> GainRamp {
+        GainRamp {
+            gain: f64::from(gain),
+        }
+    }
+
+    /// Multiply `frame_count` frames of the samples in `areas` by the gain, moving it
+    /// towards `target`. This does nothing once the gain has reached 1.0.
+    ///
+    /// The areas must be valid for `frame_count` frames of `format`.
+    pub(crate) unsafe fn apply(
+        &mut self,
+        target: f32,
+        areas: &[raw::SoundIoChannelArea],
+        format: Format,
+        sample_rate: i32,
+        frame_count: usize,
+    ) {
+        let target = f64::from(target);
+        if self.gain == 1.0 && target == 1.0 {
+            return;
+        }
+
+        let read = read_fn::<f64>(format);
+        let write = write_fn::<f64>(format);
+        let step = 1.0 / (RAMP_TIME * f64::from(sample_rate.max(1)));
+        for frame in 0..frame_count {
+            self.gain = next_gain(self.gain, target, step);
+            for area in areas {
+                let ptr = area.ptr.add(frame * area.step as usize) as *mut u8;
+                write(read(ptr) * self.gain, ptr);
+            }
+        }
+    }
+}
+
+// Move `gain` towards `target` by at most `step`.
+fn next_gain(gain: f64, target: f64, step: f64) -> f64 {
+    if gain < target {
+        (gain + step).min(target)
+    } else {
+        (gain - step).max(target)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::os::raw::{c_char, c_int};
+
+    // Interleaved areas for `samples`.
+    fn areas(samples: &mut [f32], channel_count: usize) -> Vec<raw::SoundIoChannelArea> {
+        (0..channel_count)
+            .map(|c| raw::SoundIoChannelArea {
+                ptr: unsafe { samples.as_mut_ptr().add(c) as *mut c_char },
+                step: (channel_count * 4) as c_int,
+            })
+            .collect()
+    }
+
+    #[test]
+    fn ramp() {
+        // At 1000 Hz the gain moves by 0.05 per frame.
+        let mut samples = vec![1.0f32; 2 * 30];
+        let mut ramp = GainRamp::new(1.0);
+        unsafe {
+            let areas = areas(&mut samples, 2);
+            ramp.apply(0.5, &areas, f32::FORMAT, 1000, 30);
+        }
+        let left: Vec<f32> = samples.iter().step_by(2).cloned().collect();
+        let right: Vec<f32> = samples.iter().skip(1).step_by(2).cloned().collect();
+        assert_eq!(left, right);
+        for (f, &s) in left.iter().enumerate() {
+            let expected = (1.0 - 0.05 * (f + 1) as f32).max(0.5);
+            assert!((s - expected).abs() < 1e-6, "{} != {}", s, expected);
+        }
+        assert_eq!(ramp.gain, 0.5);
+
+        // Steady gain.
+        let mut samples = vec![0.5f32; 4];
+        unsafe {
+            let areas = areas(&mut samples, 1);
+            ramp.apply(0.5, &areas, f32::FORMAT, 1000, 4);
+        }
+        assert_eq!(samples, vec![0.25; 4]);
+    }
+
+    #[test]
+    fn unity_is_untouched() {
+        let mut samples = vec![0.1f32, -0.2, 0.3];
+        let mut ramp = GainRamp::new(1.0);
+        unsafe {
+            let areas = areas(&mut samples, 1);
+            ramp.apply(1.0, &areas, f32::FORMAT, 48000, 3);
+        }
+        assert_eq!(samples, vec![0.1, -0.2, 0.3]);
+    }
+
+    #[test]
+    fn fallback_to_software() {
+        let volume = VolumeControl::new(1.0, false);
+        match volume.set(1.5, |_| Ok(())) {
+            Err(Error::Invalid) => {}
+            r => panic!("Unexpected {:?}", r),
+        }
+        volume
+            .set(0.5, |v| {
+                assert_eq!(v, 0.5);
+                Ok(())
+            })
+            .unwrap();
+        assert!(!volume.is_software());
+
+        volume
+            .set(0.25, |_| Err(Error::IncompatibleBackend))
+            .unwrap();
+        assert!(volume.is_software());
+        assert_eq!(volume.volume(), 0.25);
+        volume
+            .set(0.75, |_| panic!("Used the backend volume"))
+            .unwrap();
+        assert_eq!(volume.volume(), 0.75);
+    }
+}
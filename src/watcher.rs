@@ -0,0 +1,312 @@
+use super::context::*;
+use super::device::*;
+use super::error::*;
+use super::format::*;
+use super::layout::*;
+use super::types::*;
+
+/// The properties of a device at the time it was seen by a `DeviceWatcher`. Unlike
+/// `Device` it doesn't borrow the `Context`, so it stays valid after the device list
+/// changes.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DeviceSnapshot {
+    /// See `Device::id()`.
+    pub id: String,
+    /// See `Device::name()`.
+    pub name: String,
+    /// See `Device::aim()`.
+    pub aim: DeviceAim,
+    /// See `Device::is_raw()`.
+    pub is_raw: bool,
+    /// See `Device::layouts()`.
+    pub layouts: Vec<ChannelLayout>,
+    /// See `Device::formats()`.
+    pub formats: Vec<Format>,
+    /// See `Device::sample_rates()`.
+    pub sample_rates: Vec<SampleRateRange>,
+}
+
+impl DeviceSnapshot {
+    /// Returns true if `other` is the same device, i.e. it has the same id, aim and raw
+    /// mode. A physical device can appear as up to four devices with the same id, one
+    /// for each aim and raw mode.
+    pub fn is_same_device(&self, other: &DeviceSnapshot) -> bool {
+        self.id == other.id && self.aim == other.aim && self.is_raw == other.is_raw
+    }
+}
+
+impl<'a, 'b> From<&'b Device<'a>> for DeviceSnapshot {
+    fn from(device: &'b Device<'a>) -> DeviceSnapshot {
+        DeviceSnapshot {
+            id: device.id(),
+            name: device.name(),
+            aim: device.aim(),
+            is_raw: device.is_raw(),
+            layouts: device.layouts(),
+            formats: device.formats(),
+            sample_rates: device.sample_rates(),
+        }
+    }
+}
+
+/// A change to the devices, found by `DeviceWatcher::update()`.
+#[derive(Debug, Clone)]
+pub enum DeviceChange {
+    /// A device was connected.
+    Added(DeviceSnapshot),
+    /// A device was disconnected.
+    Removed(DeviceSnapshot),
+    /// The default device for `aim` changed. `None` means there is no default device.
+    DefaultChanged {
+        /// Whether this is the default input or output device.
+        aim: DeviceAim,
+        /// The previous default device.
+        previous: Option<DeviceSnapshot>,
+        /// The new default device.
+        current: Option<DeviceSnapshot>,
+    },
+    /// The name, layouts, formats or sample rates of a device changed.
+    CapabilitiesChanged {
+        /// The device before the change.
+        previous: DeviceSnapshot,
+        /// The device after the change.
+        current: DeviceSnapshot,
+    },
+}
+
+/// `DeviceWatcher` keeps a snapshot of the input and output devices of a `Context`, and
+/// compares it with the current devices whenever they change, so that a device picker
+/// can be updated with the devices that were added or removed.
+///
+/// libsoundio only updates the devices when the context flushes its events, so either
+/// call `Context::flush_events()` before `update()` or use `Context::start_event_thread()`.
+/// `update()` only rescans the devices if the context's `EventSink` has recorded an
+/// `Event::DevicesChanged` since the last scan, so it is cheap to call often.
+///
+/// # Examples
+///
+/// ```
+/// # fn foo() -> Result<(), soundio::Error> {
+/// let mut ctx = soundio::Context::new();
+/// ctx.connect()?;
+/// ctx.flush_events();
+///
+/// let mut watcher = soundio::DeviceWatcher::new(&ctx)?;
+/// for dev in watcher.devices() {
+///     println!("{:?} device: {}", dev.aim, dev.name);
+/// }
+///
+/// loop {
+///     ctx.wait_events();
+///     for change in watcher.update(&ctx)? {
+///         match change {
+///             soundio::DeviceChange::Added(dev) => println!("Added {}", dev.name),
+///             soundio::DeviceChange::Removed(dev) => println!("Removed {}", dev.name),
+///             change => println!("{:?}", change),
+///         }
+///     }
+/// }
+/// # }
+/// ```
+pub struct DeviceWatcher {
+    devices: Vec<DeviceSnapshot>,
+    default_input: Option<DeviceSnapshot>,
+    default_output: Option<DeviceSnapshot>,
+    // `EventSink::devices_change_count()` at the last scan.
+    devices_change_count: usize,
+}
+
+impl DeviceWatcher {
+    /// Take a snapshot of the current devices.
+    ///
+    /// # Errors
+    ///
+    /// The errors returned by `Context::input_devices()` and `Context::output_devices()`.
+    pub fn new(ctx: &Context) -> Result<DeviceWatcher> {
+        let mut watcher = DeviceWatcher {
+            devices: Vec::new(),
+            default_input: None,
+            default_output: None,
+            devices_change_count: 0,
+        };
+        watcher.rescan(ctx)?;
+        Ok(watcher)
+    }
+
+    /// The devices in the most recent snapshot, inputs first.
+    pub fn devices(&self) -> &[DeviceSnapshot] {
+        &self.devices
+    }
+
+    /// The default input or output device in the most recent snapshot.
+    pub fn default_device(&self, aim: DeviceAim) -> Option<&DeviceSnapshot> {
+        match aim {
+            DeviceAim::Input => self.default_input.as_ref(),
+            DeviceAim::Output => self.default_output.as_ref(),
+        }
+    }
+
+    /// If the devices have changed since the last scan, take a new snapshot and return
+    /// the changes. Otherwise this returns no changes without scanning the devices.
+    ///
+    /// # Errors
+    ///
+    /// See `DeviceWatcher::new()`. The snapshot is not changed if there is an error.
+    pub fn update(&mut self, ctx: &Context) -> Result<Vec<DeviceChange>> {
+        if ctx.event_sink().devices_change_count() == self.devices_change_count {
+            return Ok(Vec::new());
+        }
+        self.rescan(ctx)
+    }
+
+    /// Take a new snapshot and return the changes, even if the context hasn't recorded a
+    /// change. This is useful after `Context::force_device_scan()`.
+    ///
+    /// # Errors
+    ///
+    /// See `DeviceWatcher::new()`. The snapshot is not changed if there is an error.
+    pub fn rescan(&mut self, ctx: &Context) -> Result<Vec<DeviceChange>> {
+        let devices_change_count = ctx.event_sink().devices_change_count();
+
+        let mut devices: Vec<DeviceSnapshot> = ctx
+            .input_devices()?
+            .iter()
+            .map(DeviceSnapshot::from)
+            .collect();
+        devices.extend(ctx.output_devices()?.iter().map(DeviceSnapshot::from));
+        let default_input = default_snapshot(ctx.default_input_device())?;
+        let default_output = default_snapshot(ctx.default_output_device())?;
+
+        let mut changes = diff_devices(&self.devices, &devices);
+        if !same_device(&self.default_input, &default_input) {
+            changes.push(DeviceChange::DefaultChanged {
+                aim: DeviceAim::Input,
+                previous: self.default_input.take(),
+                current: default_input.clone(),
+            });
+        }
+        if !same_device(&self.default_output, &default_output) {
+            changes.push(DeviceChange::DefaultChanged {
+                aim: DeviceAim::Output,
+                previous: self.default_output.take(),
+                current: default_output.clone(),
+            });
+        }
+
+        self.devices = devices;
+        self.default_input = default_input;
+        self.default_output = default_output;
+        self.devices_change_count = devices_change_count;
+        Ok(changes)
+    }
+}
+
+// A snapshot of the default device, or `None` if there isn't one.
+fn default_snapshot(device: Result<Device<'_>>) -> Result<Option<DeviceSnapshot>> {
+    match device {
+        Ok(device) => Ok(Some(DeviceSnapshot::from(&device))),
+        Err(Error::NoSuchDevice) => Ok(None),
+        Err(e) => Err(e),
+    }
+}
+
+fn same_device(a: &Option<DeviceSnapshot>, b: &Option<DeviceSnapshot>) -> bool {
+    match (a, b) {
+        (Some(a), Some(b)) => a.is_same_device(b),
+        (None, None) => true,
+        _ => false,
+    }
+}
+
+// The devices that were removed, then those that were added, then those that changed.
+fn diff_devices(previous: &[DeviceSnapshot], current: &[DeviceSnapshot]) -> Vec<DeviceChange> {
+    let mut changes: Vec<DeviceChange> = previous
+        .iter()
+        .filter(|p| !current.iter().any(|c| c.is_same_device(p)))
+        .map(|p| DeviceChange::Removed(p.clone()))
+        .collect();
+    let mut changed = Vec::new();
+    for c in current {
+        match previous.iter().find(|p| p.is_same_device(c)) {
+            None => changes.push(DeviceChange::Added(c.clone())),
+            Some(p) if p != c => changed.push(DeviceChange::CapabilitiesChanged {
+                previous: p.clone(),
+                current: c.clone(),
+            }),
+            Some(_) => {}
+        }
+    }
+    changes.extend(changed);
+    changes
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn device(id: &str, aim: DeviceAim, is_raw: bool) -> DeviceSnapshot {
+        DeviceSnapshot {
+            id: id.to_string(),
+            name: id.to_string(),
+            aim,
+            is_raw,
+            layouts: vec![ChannelLayout::get_builtin(ChannelLayoutId::Stereo)],
+            formats: vec![Format::Float32LE],
+            sample_rates: vec![SampleRateRange {
+                min: 44100,
+                max: 48000,
+            }],
+        }
+    }
+
+    fn describe(changes: &[DeviceChange]) -> Vec<String> {
+        changes
+            .iter()
+            .map(|c| match c {
+                DeviceChange::Added(d) => format!("+{}", d.id),
+                DeviceChange::Removed(d) => format!("-{}", d.id),
+                DeviceChange::CapabilitiesChanged { current, .. } => format!("~{}", current.id),
+                DeviceChange::DefaultChanged { .. } => "default".to_string(),
+            })
+            .collect()
+    }
+
+    #[test]
+    fn added_removed_changed() {
+        let previous = vec![
+            device("a", DeviceAim::Output, false),
+            device("b", DeviceAim::Output, false),
+            device("c", DeviceAim::Input, false),
+        ];
+        let mut changed = device("c", DeviceAim::Input, false);
+        changed.sample_rates[0].max = 96000;
+        let current = vec![
+            device("d", DeviceAim::Output, false),
+            changed,
+            device("a", DeviceAim::Output, false),
+        ];
+        assert_eq!(
+            describe(&diff_devices(&previous, &current)),
+            ["-b", "+d", "~c"]
+        );
+        assert!(diff_devices(&current, &current).is_empty());
+    }
+
+    #[test]
+    fn same_id_different_device() {
+        // The input and raw devices of a physical device share its id.
+        let previous = vec![device("a", DeviceAim::Output, false)];
+        let current = vec![
+            device("a", DeviceAim::Output, false),
+            device("a", DeviceAim::Input, false),
+            device("a", DeviceAim::Output, true),
+        ];
+        assert_eq!(describe(&diff_devices(&previous, &current)), ["+a", "+a"]);
+
+        let output = Some(device("a", DeviceAim::Output, false));
+        let raw = Some(device("a", DeviceAim::Output, true));
+        assert!(same_device(&output, &output));
+        assert!(!same_device(&output, &raw));
+        assert!(!same_device(&output, &None));
+    }
+}